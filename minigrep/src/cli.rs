@@ -0,0 +1,312 @@
+// 命令行参数的词法分析。
+// Parser只负责把参数切分成选项和值，具体每个选项的含义由Config::new决定。
+// 支持的写法：
+// • 短选项可以合并：-inb 等价于 -i -n -b
+// • 短选项的值可以紧跟在后面：-A3、-nA3，也可以是下一个参数：-A 3
+// • 长选项的值可以用'='连接：--include=*.rs，也可以是下一个参数：--include *.rs
+// • 单独的"--"之后的参数全部被当作普通值，单独的"-"也是普通值
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub const USAGE: &str = "\
+Usage: minigrep [OPTIONS] <QUERY> [PATH]...
+       minigrep [OPTIONS] -e <PATTERN>... [PATH]...
+       minigrep index build [DIR]...
+
+Search for QUERY in each PATH. Directories are searched recursively.
+With no PATH, or when PATH is -, read standard input.
+With -e or -f, every positional argument is a PATH and a line matches
+if any of the patterns matches.
+'minigrep index build' creates or updates a trigram index in each DIR
+(default: the current directory) for use with --index.
+
+Options:
+  -E, --regex              treat QUERY as a regular expression
+  -e, --regexp <PATTERN>   search for PATTERN; may be given more than once
+  -f, --file <FILE>        read patterns from FILE, one per line
+  -i, --ignore-case        case-insensitive search
+  -s, --case-sensitive     case-sensitive search (the default)
+  -S, --smart-case         case-insensitive unless QUERY contains an uppercase letter
+  -w, --word-regexp        only match whole words
+  -U, --multiline          let patterns match across lines and print every line of each match
+      --csv                parse inputs as CSV and only match inside the --field column
+      --tsv                parse inputs as tab-separated values, like --csv
+      --field <NAME|N>     the column to search: a name from the header line, or a 1-based index
+      --jsonl              parse each line as JSON and only match the value at --path
+      --path <PATH>        the JSON path to search, e.g. .user.name or .items.0
+  -n, --line-number        print line numbers
+  -b, --byte-offset        print the byte offset of each line
+  -z, --decompress         search inside gzip, zstd and bzip2 compressed inputs
+      --encoding <ENC>     decode inputs without a BOM as ENC (e.g. gbk, latin1; default: utf-8)
+      --lossy              replace undecodable bytes with U+FFFD instead of failing
+  -A, --after-context <N>  print N lines after each match
+  -B, --before-context <N> print N lines before each match
+  -C, --context <N>        print N lines before and after each match
+  -v, --invert-match       select non-matching lines
+  -c, --count              print only a count of selected lines per file
+  -l, --files-with-matches print only names of files with selected lines
+  -L, --files-without-match
+                           print only names of files with no selected lines
+  -m, --max-count <N>      stop reading a file after N selected lines
+  -q, --quiet              print nothing, exit 0 on any match
+  -j, --threads <N>        search files with N threads (default: number of CPUs)
+      --json               print results as JSON Lines (begin/match/context/end/summary)
+      --replace <TEXT>     print lines with matches replaced by TEXT ($1, ${name} in regex mode)
+      --in-place[=SUFFIX]  write replacements back to the files, keeping a backup if SUFFIX is given
+      --dry-run            with --in-place, print a diff instead of modifying files
+      --watch              keep running and search again whenever the files change
+      --follow             keep running and search lines appended to the files, like tail -f
+      --fuzzy <K>          match substrings within edit distance K of QUERY
+      --rank               with --fuzzy, print the closest matches of each file first
+      --color[=WHEN]       highlight matches; WHEN is auto, always or never
+      --include <GLOB>     only search files matching GLOB
+      --exclude <GLOB>     skip files and directories matching GLOB
+  -t, --type <TYPE>        only search files of TYPE (see --type-list)
+  -T, --type-not <TYPE>    skip files of TYPE
+      --type-add <NAME:GLOB[,GLOB]>
+                           define a file type, or add globs to an existing one
+      --type-list          print all file types and exit
+      --hidden             search hidden files and directories
+      --no-ignore          don't respect .gitignore, .ignore or global git excludes, and search hidden files
+      --no-config          don't read the configuration file
+      --index <DIR>        only search files in DIR that may match according to its index
+                           (default PATH: DIR)
+  -h, --help               print this help and exit
+  -V, --version            print version information and exit
+
+Defaults are read from $MINIGREP_CONFIG, or ~/.config/minigrep/config.toml.
+Options on the command line override the CASE_INSENSITIVE environment variable,
+which in turn overrides the configuration file.
+
+Exit status is 0 if any line is selected, 1 otherwise, and 2 if an error occurred.
+";
+
+// Config::new可能返回的错误。
+// HelpRequested和VersionRequested并不是真正的错误，main会把它们当作正常退出处理。
+#[derive(Debug)]
+pub enum ConfigError {
+    NotEnoughArgs,
+    UnknownFlag(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue { flag: String, value: String },
+    InvalidRegex { pattern: String, source: regex::Error },
+    // -f给出的模式文件无法读取
+    PatternFile { path: String, source: io::Error },
+    // 配置文件无法读取或者格式不对
+    ConfigFile { path: String, message: String },
+    // 模式太多，超出了Aho-Corasick自动机的大小限制
+    PatternSet(aho_corasick::BuildError),
+    ConflictingFlags(&'static str, &'static str),
+    // 第一个选项需要和第二个选项一起使用
+    MissingFlag(&'static str, &'static str),
+    HelpRequested,
+    VersionRequested,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::NotEnoughArgs => write!(f, "missing QUERY"),
+            ConfigError::UnknownFlag(flag) => write!(f, "unknown flag '{}'", flag),
+            ConfigError::MissingValue(flag) => write!(f, "flag '{}' requires a value", flag),
+            ConfigError::UnexpectedValue(flag) => write!(f, "flag '{}' doesn't take a value", flag),
+            ConfigError::InvalidValue { flag, value } => {
+                write!(f, "invalid value '{}' for flag '{}'", value, flag)
+            }
+            // 带有source的错误只描述这一层，底层的原因由source()给出
+            ConfigError::InvalidRegex { pattern, .. } => write!(f, "invalid regex '{}'", pattern),
+            ConfigError::PatternFile { path, .. } => write!(f, "cannot read patterns from '{}'", path),
+            ConfigError::ConfigFile { path, message } => write!(f, "config file '{}': {}", path, message),
+            ConfigError::PatternSet(_) => write!(f, "cannot build pattern set"),
+            ConfigError::ConflictingFlags(a, b) => write!(f, "{} cannot be used with {}", a, b),
+            ConfigError::MissingFlag(a, b) => write!(f, "{} requires {}", a, b),
+            ConfigError::HelpRequested => write!(f, "help requested"),
+            ConfigError::VersionRequested => write!(f, "version requested"),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::InvalidRegex { source, .. } => Some(source),
+            ConfigError::PatternFile { source, .. } => Some(source),
+            ConfigError::PatternSet(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Arg {
+    // 选项统一写成带横线的形式，比如"-n"、"--line-number"
+    Flag(String),
+    Value(String),
+}
+
+pub struct Parser {
+    args: Vec<String>,
+    pos: usize,
+    // 正在拆分的短选项组中还没有处理的字符，比如-inb处理完i之后剩下"nb"
+    shorts: Option<String>,
+    // --flag=value中'='后面的部分，等待value()取走
+    long_value: Option<String>,
+    last_flag: String,
+    only_values: bool,
+}
+
+impl Parser {
+    // args[0]是程序名，会被跳过
+    pub fn new(args: &[String]) -> Parser {
+        Parser {
+            args: args.iter().skip(1).cloned().collect(),
+            pos: 0,
+            shorts: None,
+            long_value: None,
+            last_flag: String::new(),
+            only_values: false,
+        }
+    }
+
+    pub fn next_arg(&mut self) -> Result<Option<Arg>, ConfigError> {
+        if self.long_value.take().is_some() {
+            return Err(ConfigError::UnexpectedValue(self.last_flag.clone()));
+        }
+
+        if let Some(rest) = self.shorts.take() {
+            let mut chars = rest.chars();
+            if let Some(c) = chars.next() {
+                let rest = chars.as_str();
+                if !rest.is_empty() {
+                    self.shorts = Some(rest.to_string());
+                }
+                self.last_flag = format!("-{}", c);
+                return Ok(Some(Arg::Flag(self.last_flag.clone())));
+            }
+        }
+
+        let arg = match self.args.get(self.pos) {
+            Some(arg) => arg.clone(),
+            None => return Ok(None),
+        };
+        self.pos += 1;
+
+        if self.only_values || arg == "-" || !arg.starts_with('-') {
+            return Ok(Some(Arg::Value(arg)));
+        }
+        if arg == "--" {
+            self.only_values = true;
+            return self.next_arg();
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            self.long_value = value;
+            self.last_flag = format!("--{}", name);
+            return Ok(Some(Arg::Flag(self.last_flag.clone())));
+        }
+
+        self.shorts = Some(arg[1..].to_string());
+        self.next_arg()
+    }
+
+    // 取出最近一个选项的值
+    pub fn value(&mut self) -> Result<String, ConfigError> {
+        if let Some(value) = self.long_value.take() {
+            return Ok(value);
+        }
+        if let Some(rest) = self.shorts.take() {
+            return Ok(rest);
+        }
+        match self.args.get(self.pos) {
+            Some(value) => {
+                self.pos += 1;
+                Ok(value.clone())
+            }
+            None => Err(ConfigError::MissingValue(self.last_flag.clone())),
+        }
+    }
+
+    // 只取出用'='连接的值，用于--color这种值可以省略的长选项
+    pub fn optional_value(&mut self) -> Option<String> {
+        self.long_value.take()
+    }
+
+    // 取出最近一个选项的值并解析成数字
+    pub fn number(&mut self) -> Result<usize, ConfigError> {
+        let value = self.value()?;
+        value.parse().map_err(|_| ConfigError::InvalidValue {
+            flag: self.last_flag.clone(),
+            value,
+        })
+    }
+
+    pub fn last_flag(&self) -> &str {
+        &self.last_flag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Result<Vec<Arg>, ConfigError> {
+        let args: Vec<String> = list.iter().map(|s| s.to_string()).collect();
+        let mut parser = Parser::new(&args);
+        let mut parsed = Vec::new();
+        while let Some(arg) = parser.next_arg()? {
+            let takes_value = matches!(&arg, Arg::Flag(f) if f == "-A" || f == "--include");
+            parsed.push(arg);
+            if takes_value {
+                parsed.push(Arg::Value(parser.value()?));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(f: &str) -> Arg {
+        Arg::Flag(f.to_string())
+    }
+
+    fn value(v: &str) -> Arg {
+        Arg::Value(v.to_string())
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        assert_eq!(
+            vec![flag("-i"), flag("-n"), flag("-A"), value("3"), value("x")],
+            parse(&["minigrep", "-inA3", "x"]).unwrap()
+        );
+        assert_eq!(
+            vec![flag("-A"), value("2"), value("-")],
+            parse(&["minigrep", "-A", "2", "-"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn long_flags_and_double_dash() {
+        assert_eq!(
+            vec![flag("--include"), value("*.rs"), value("-n"), value("--x")],
+            parse(&["minigrep", "--include=*.rs", "--", "-n", "--x"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            parse(&["minigrep", "--regex=yes"]),
+            Err(ConfigError::UnexpectedValue(f)) if f == "--regex"
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "-A"]),
+            Err(ConfigError::MissingValue(f)) if f == "-A"
+        ));
+    }
+}
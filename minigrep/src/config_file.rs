@@ -0,0 +1,171 @@
+// 配置文件：$MINIGREP_CONFIG指定的文件，没有设置时是~/.config/minigrep/config.toml。
+// 优先级从低到高依次是：配置文件、环境变量（CASE_INSENSITIVE）、命令行。
+// 命令行上的--no-config表示完全不读取配置文件。格式如下：
+//
+//   # 默认选项，写法和命令行相同，需要值的选项写成--context=2或-C2的形式
+//   flags = ["--smart-case", "-n"]
+//   # 遍历目录时总是跳过的glob，和--exclude相同
+//   ignore = ["*.min.js", "dist/"]
+//
+//   # 配色方案，值是SGR参数，没有给出的部分使用默认值
+//   [colors]
+//   path = "35"
+//   line_number = "32"
+//   byte_offset = "32"
+//   separator = "36"
+//   match = "01;31"
+//
+//   # 自定义的文件类型，名称对应一组glob
+//   [types]
+//   web = ["*.html", "*.css", "*.js"]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use toml::{Table, Value};
+
+use crate::output::Colors;
+use crate::ConfigError;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct FileConfig {
+    // 配置文件的路径，报告flags中的错误时使用
+    pub path: String,
+    pub flags: Vec<String>,
+    pub ignore: Vec<String>,
+    pub colors: Colors,
+    pub types: Vec<(String, Vec<String>)>,
+}
+
+// 找到并解析配置文件。默认位置的文件不存在时返回默认配置，
+// 而$MINIGREP_CONFIG指定的文件不存在则是一个错误。
+pub fn load() -> Result<FileConfig, ConfigError> {
+    let (path, required) = match env::var_os("MINIGREP_CONFIG") {
+        Some(path) => (PathBuf::from(path), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(FileConfig::default()),
+        },
+    };
+    let error = |message: String| ConfigError::ConfigFile {
+        path: path.display().to_string(),
+        message,
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) if !required && !path.exists() => return Ok(FileConfig::default()),
+        Err(err) => return Err(error(err.to_string())),
+    };
+    let mut config = parse(&contents).map_err(error)?;
+    config.path = path.display().to_string();
+    Ok(config)
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("minigrep").join("config.toml"))
+}
+
+pub fn parse(contents: &str) -> Result<FileConfig, String> {
+    let table: Table = contents.parse().map_err(|err: toml::de::Error| err.message().to_string())?;
+    let mut config = FileConfig::default();
+    for (key, value) in &table {
+        match key.as_str() {
+            "flags" => {
+                config.flags = strings(key, value)?;
+                // 配置文件中只能有选项，query和路径只能来自命令行。
+                // "--"会让命令行中的选项全部变成普通参数，"-"是标准输入，都不是选项
+                if let Some(flag) = config
+                    .flags
+                    .iter()
+                    .find(|flag| !flag.starts_with('-') || *flag == "-" || *flag == "--")
+                {
+                    return Err(format!("'{}' in flags is not an option", flag));
+                }
+            }
+            "ignore" => config.ignore = strings(key, value)?,
+            "colors" => {
+                for (name, value) in table_of(key, value)? {
+                    let sgr = value
+                        .as_str()
+                        .ok_or_else(|| format!("colors.{} must be a string", name))?
+                        .to_string();
+                    match name.as_str() {
+                        "path" => config.colors.path = sgr,
+                        "line_number" => config.colors.line_number = sgr,
+                        "byte_offset" => config.colors.byte_offset = sgr,
+                        "separator" => config.colors.separator = sgr,
+                        "match" => config.colors.matched = sgr,
+                        _ => return Err(format!("unknown key 'colors.{}'", name)),
+                    }
+                }
+            }
+            "types" => {
+                for (name, globs) in table_of(key, value)? {
+                    config.types.push((name.clone(), strings(&format!("types.{}", name), globs)?));
+                }
+            }
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+    Ok(config)
+}
+
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    let error = || format!("{} must be an array of strings", key);
+    value
+        .as_array()
+        .ok_or_else(error)?
+        .iter()
+        .map(|item| item.as_str().map(String::from).ok_or_else(error))
+        .collect()
+}
+
+fn table_of<'v>(key: &str, value: &'v Value) -> Result<&'v Table, String> {
+    value.as_table().ok_or_else(|| format!("{} must be a table", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_config() {
+        let config = parse(
+            r#"
+flags = ["--smart-case", "-n"]
+ignore = ["*.min.js"]
+
+[colors]
+match = "01;32"
+
+[types]
+web = ["*.html", "*.css"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(vec!["--smart-case", "-n"], config.flags);
+        assert_eq!(vec!["*.min.js"], config.ignore);
+        assert_eq!("01;32", config.colors.matched);
+        assert_eq!("35", config.colors.path);
+        assert_eq!(
+            vec![(String::from("web"), vec![String::from("*.html"), String::from("*.css")])],
+            config.types
+        );
+        assert_eq!(FileConfig::default(), parse("").unwrap());
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert_eq!("unknown key 'flag'", parse("flag = []").unwrap_err());
+        assert_eq!("'body' in flags is not an option", parse(r#"flags = ["-w", "body"]"#).unwrap_err());
+        assert_eq!("'--' in flags is not an option", parse(r#"flags = ["--"]"#).unwrap_err());
+        assert_eq!("'-' in flags is not an option", parse(r#"flags = ["-n", "-"]"#).unwrap_err());
+        assert_eq!("ignore must be an array of strings", parse("ignore = [1]").unwrap_err());
+        assert_eq!("unknown key 'colors.mt'", parse("[colors]\nmt = \"1\"").unwrap_err());
+        assert!(parse("flags = [").is_err());
+    }
+}
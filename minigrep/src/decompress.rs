@@ -0,0 +1,152 @@
+// -z：透明地解压缩输入。
+// 根据文件开头的魔数（而不是扩展名）判断压缩格式，解压后的内容再交给逐行搜索，
+// 所以行号和字节偏移都是相对于解压后的内容而言的。
+// 解压出错时，错误信息中会带上压缩格式，比如"gzip: invalid gzip header"，
+// 这类错误的类型都是InvalidData，调用者据此把它们报告为无法解码的文件。
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use bzip2::read::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Codec {
+    // 根据开头的几个字节判断压缩格式，不是已知的格式时返回None
+    pub fn detect(header: &[u8]) -> Option<Codec> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else if is_bzip2(header) {
+            Some(Codec::Bzip2)
+        } else {
+            None
+        }
+    }
+}
+
+// "BZh"和块大小'1'..'9'之后是第一个块的魔数（π的BCD码），空的流则直接是结束标记（√π的BCD码）。
+// 只看"BZh"的话，以"BZh"开头的普通文本也会被当作bzip2
+fn is_bzip2(header: &[u8]) -> bool {
+    const BLOCK: &[u8] = &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+    const END: &[u8] = &[0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+    match header {
+        [b'B', b'Z', b'h', b'1'..=b'9', magic @ ..] => magic.starts_with(BLOCK) || magic.starts_with(END),
+        _ => false,
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Codec::Gzip => write!(f, "gzip"),
+            Codec::Zstd => write!(f, "zstd"),
+            Codec::Bzip2 => write!(f, "bzip2"),
+        }
+    }
+}
+
+// 如果reader的内容是压缩过的，返回解压后的reader，否则原样返回
+pub fn maybe_decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let codec = match Codec::detect(reader.fill_buf()?) {
+        Some(codec) => codec,
+        None => return Ok(Box::new(reader)),
+    };
+    let context = codec.to_string();
+    let decoder: Box<dyn Read + 'a> = match codec {
+        // 多个gzip/bzip2成员首尾相连时（比如cat a.gz b.gz）全部解压
+        Codec::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Codec::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+        Codec::Zstd => {
+            let decoder = StreamingDecoder::new(reader)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", context, err)))?;
+            Box::new(decoder)
+        }
+    };
+    Ok(Box::new(BufReader::new(WithContext { inner: decoder, context })))
+}
+
+// 给解压过程中产生的错误加上压缩格式，数据不完整或者格式不对都算作InvalidData
+struct WithContext<R> {
+    inner: R,
+    context: String,
+}
+
+impl<R: Read> Read for WithContext<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner
+            .read(buf)
+            .map_err(|err| {
+                let kind = match err.kind() {
+                    io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof => io::ErrorKind::InvalidData,
+                    kind => kind,
+                };
+                // bzip2的错误信息本身已经以"bzip2: "开头
+                let message = err.to_string();
+                match message.strip_prefix(self.context.as_str()) {
+                    Some(rest) if rest.starts_with(": ") => io::Error::new(kind, message),
+                    _ => io::Error::new(kind, format!("{}: {}", self.context, message)),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const TEXT: &str = "Rust:\nsafe, fast, productive.\nPick three.\n";
+
+    fn decompress(bytes: &[u8]) -> io::Result<String> {
+        let mut out = String::new();
+        maybe_decompress(bytes)?.read_to_string(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn detects_and_decompresses() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(TEXT.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(Some(Codec::Gzip), Codec::detect(&gzip));
+        assert_eq!(TEXT, decompress(&gzip).unwrap());
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(TEXT.as_bytes()).unwrap();
+        let bzip2 = bzip2.finish().unwrap();
+        assert_eq!(Some(Codec::Bzip2), Codec::detect(&bzip2));
+        assert_eq!(TEXT, decompress(&bzip2).unwrap());
+
+        let zstd = ruzstd::encoding::compress_to_vec(TEXT.as_bytes(), ruzstd::encoding::CompressionLevel::Fastest);
+        assert_eq!(Some(Codec::Zstd), Codec::detect(&zstd));
+        assert_eq!(TEXT, decompress(&zstd).unwrap());
+
+        // 未压缩的内容原样返回，以"BZh"开头的文本也不例外
+        assert_eq!(TEXT, decompress(TEXT.as_bytes()).unwrap());
+        assert_eq!(None, Codec::detect(b"BZhello\n"));
+        assert_eq!("BZhello\n", decompress(b"BZhello\n").unwrap());
+        let empty = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default()).finish().unwrap();
+        assert_eq!(Some(Codec::Bzip2), Codec::detect(&empty));
+    }
+
+    #[test]
+    fn errors_name_codec() {
+        let err = decompress(b"\x1f\x8b\x08\x00garbage").unwrap_err();
+        assert!(err.to_string().starts_with("gzip: "), "{}", err);
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        let err = decompress(b"BZh9\x31\x41\x59\x26\x53\x59garbage").unwrap_err();
+        assert!(err.to_string().starts_with("bzip2: ") && !err.to_string().starts_with("bzip2: bzip2"), "{}", err);
+        let err = decompress(b"\x28\xb5\x2f\xfd\xff").unwrap_err();
+        assert!(err.to_string().starts_with("zstd: "), "{}", err);
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
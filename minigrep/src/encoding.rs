@@ -0,0 +1,157 @@
+// 把非UTF-8的输入转码为UTF-8后再搜索。
+// • 文件以BOM开头时，按照BOM指明的编码（UTF-8、UTF-16LE、UTF-16BE）解码，BOM本身会被去掉
+// • --encoding指定了编码（比如GBK、Latin-1）时，没有BOM的输入都按照这个编码解码
+// • 无法解码的字节是一个InvalidData错误；--lossy时它们被替换为U+FFFD，而不是报错
+// 转码之后行号不变，但-b打印的字节偏移是相对于转码后的UTF-8内容的。
+
+use std::io::{self, BufRead};
+
+use encoding_rs::{Decoder, DecoderResult, Encoding, UTF_8};
+
+// 根据--encoding的值查找编码，"auto"表示只根据BOM判断
+pub fn parse(label: &str) -> Option<Option<&'static Encoding>> {
+    if label.eq_ignore_ascii_case("auto") {
+        return Some(None);
+    }
+    Encoding::for_label(label.as_bytes()).map(Some)
+}
+
+// 需要转码时返回转码后的reader，否则原样返回，普通的UTF-8文件不会有额外的开销
+pub fn maybe_decode<'a, R: BufRead + 'a>(
+    mut reader: R,
+    encoding: Option<&'static Encoding>,
+    lossy: bool,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    // lossy模式下把没有BOM的输入当作UTF-8解码，非法的字节会被替换
+    let encoding = encoding.or(if lossy { Some(UTF_8) } else { None });
+    if encoding.is_none() && Encoding::for_bom(reader.fill_buf()?).is_none() {
+        return Ok(Box::new(reader));
+    }
+    // new_decoder会先检查BOM，有BOM时按BOM的编码解码并去掉BOM；没有指定编码时只有带BOM的输入会走到这里
+    let decoder = encoding.unwrap_or(UTF_8).new_decoder();
+    Ok(Box::new(Decode {
+        reader,
+        decoder,
+        lossy,
+        out: Vec::new(),
+        pos: 0,
+        done: false,
+    }))
+}
+
+// 逐块转码的reader，转码后的UTF-8内容放在out中等待读取
+struct Decode<R> {
+    reader: R,
+    decoder: Decoder,
+    lossy: bool,
+    out: Vec<u8>,
+    pos: usize,
+    // 输入已经读完，解码器中剩下的内容也已经输出
+    done: bool,
+}
+
+impl<R: BufRead> Decode<R> {
+    // 转码下一块输入。一个字符可能被分在两块中，这时它留在解码器里，本次输出可能为空
+    fn refill(&mut self) -> io::Result<()> {
+        let input = self.reader.fill_buf()?;
+        let last = input.is_empty();
+        let max = self.decoder.max_utf8_buffer_length(input.len()).unwrap_or(usize::MAX);
+        self.out.resize(max, 0);
+        let (read, written) = if self.lossy {
+            let (_, read, written, _) = self.decoder.decode_to_utf8(input, &mut self.out, last);
+            (read, written)
+        } else {
+            match self.decoder.decode_to_utf8_without_replacement(input, &mut self.out, last) {
+                (DecoderResult::Malformed(..), ..) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid {} byte sequence", self.decoder.encoding().name()),
+                    ))
+                }
+                (_, read, written) => (read, written),
+            }
+        };
+        self.reader.consume(read);
+        self.out.truncate(written);
+        self.pos = 0;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: BufRead> io::Read for Decode<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decode<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.out.len() && !self.done {
+            self.refill()?;
+        }
+        Ok(&self.out[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.out.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn decode(bytes: &[u8], encoding: Option<&'static Encoding>, lossy: bool) -> io::Result<String> {
+        let mut out = String::new();
+        maybe_decode(bytes, encoding, lossy)?.read_to_string(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn bom_sniffing() {
+        assert_eq!("Rust:\n", decode(b"\xef\xbb\xbfRust:\n", None, false).unwrap());
+        assert_eq!("参数", decode(b"\xff\xfe\xc2\x53\x70\x65", None, false).unwrap());
+        assert_eq!("参数", decode(b"\xfe\xff\x53\xc2\x65\x70", None, false).unwrap());
+        // BOM优先于--encoding
+        assert_eq!("参数", decode(b"\xff\xfe\xc2\x53\x70\x65", parse("gbk").unwrap(), false).unwrap());
+    }
+
+    #[test]
+    fn explicit_encoding() {
+        assert_eq!(Some(None), parse("AUTO"));
+        assert!(parse("no-such-encoding").is_none());
+        assert_eq!("参数", decode(b"\xb2\xce\xca\xfd", parse("GBK").unwrap(), false).unwrap());
+        assert_eq!("café", decode(b"caf\xe9", parse("latin1").unwrap(), false).unwrap());
+    }
+
+    #[test]
+    fn lossy_mode() {
+        assert!(decode(b"caf\xe9", None, false).is_err());
+        assert_eq!("caf\u{fffd}", decode(b"caf\xe9", None, true).unwrap());
+        // 指定了编码或者有BOM时，不是lossy模式也一样会报错
+        let gbk = parse("gbk").unwrap();
+        let err = decode(b"ok \xff\xff\n", gbk, false).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!("invalid GBK byte sequence", err.to_string());
+        assert_eq!("ok \u{fffd}\u{fffd}\n", decode(b"ok \xff\xff\n", gbk, true).unwrap());
+        // UTF-16LE中单独的代理项
+        let surrogate = b"\xff\xfeo\x00k\x00\x00\xd8\n\x00";
+        assert!(decode(surrogate, None, false).is_err());
+        assert_eq!("ok\u{fffd}\n", decode(surrogate, None, true).unwrap());
+    }
+
+    #[test]
+    fn characters_split_across_reads() {
+        // 每次只给解码器一个字节，字符被切开时要等到下一块输入
+        let input = io::BufReader::with_capacity(1, &b"\xb2\xce\xca\xfd\n"[..]);
+        let mut out = String::new();
+        maybe_decode(input, parse("gbk").unwrap(), false).unwrap().read_to_string(&mut out).unwrap();
+        assert_eq!("参数\n", out);
+    }
+}
@@ -0,0 +1,170 @@
+// run和build_indexes返回的错误类型。
+// 搜索多个文件时，单个文件的错误（无法打开、无法解码等）只会被打印到标准错误，搜索会继续进行，
+// 最终由Outcome::exit_code把退出码设为2；只有和具体文件无关的错误（比如写标准输出失败）才会让run提前返回。
+// 和标准库的惯例一样，Display只描述这一层错误，底层的原因通过source()取得，report()把整条链连起来打印。
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ConfigError;
+
+#[derive(Debug)]
+pub enum MinigrepError {
+    // 读写失败，path是出错的文件或目录；写标准输出等和文件无关的错误没有path
+    Io { path: Option<PathBuf>, source: io::Error },
+    // 内容无法解码：不是合法的UTF-8、无法按照--encoding解码或者压缩的数据已经损坏
+    Decode { path: PathBuf, source: io::Error },
+    // 正则表达式无法编译
+    InvalidPattern { pattern: String, source: regex::Error },
+    // 命令行用法错误
+    Usage(ConfigError),
+}
+
+impl MinigrepError {
+    // 读取path时出现的错误，InvalidData表示内容本身有问题
+    pub fn from_io(path: &Path, source: io::Error) -> MinigrepError {
+        let path = path.to_path_buf();
+        if source.kind() == io::ErrorKind::InvalidData {
+            MinigrepError::Decode { path, source }
+        } else {
+            MinigrepError::Io { path: Some(path), source }
+        }
+    }
+
+    // 和grep一样，任何错误的退出码都是2
+    pub fn exit_code(&self) -> i32 {
+        2
+    }
+
+    // 写标准输出时发现管道的另一端已经关闭
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, MinigrepError::Io { path: None, source } if source.kind() == io::ErrorKind::BrokenPipe)
+    }
+
+    // 连同所有的source一起打印，比如"a.txt: No such file or directory (os error 2)"
+    pub fn report(&self) -> Report<'_> {
+        Report(self)
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::Io { path: Some(path), .. } => write!(f, "{}", path.display()),
+            MinigrepError::Io { path: None, .. } => write!(f, "I/O error"),
+            MinigrepError::Decode { path, .. } => write!(f, "{}: cannot decode contents", path.display()),
+            MinigrepError::InvalidPattern { pattern, .. } => write!(f, "invalid regex '{}'", pattern),
+            MinigrepError::Usage(_) => write!(f, "invalid arguments"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } => Some(source),
+            MinigrepError::Decode { source, .. } => Some(source),
+            MinigrepError::InvalidPattern { source, .. } => Some(source),
+            MinigrepError::Usage(err) => Some(err),
+        }
+    }
+}
+
+impl From<ConfigError> for MinigrepError {
+    fn from(err: ConfigError) -> MinigrepError {
+        match err {
+            ConfigError::InvalidRegex { pattern, source } => MinigrepError::InvalidPattern { pattern, source },
+            ConfigError::PatternFile { path, source } => MinigrepError::Io {
+                path: Some(PathBuf::from(path)),
+                source,
+            },
+            err => MinigrepError::Usage(err),
+        }
+    }
+}
+
+// 一个错误和它的整条source链，各层之间用": "连接
+pub struct Report<'a>(pub &'a (dyn Error + 'static));
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(err) = source {
+            write!(f, ": {}", err)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+// 没有对应文件的I/O错误，主要是写标准输出失败
+impl From<io::Error> for MinigrepError {
+    fn from(source: io::Error) -> MinigrepError {
+        MinigrepError::Io { path: None, source }
+    }
+}
+
+// run成功结束时的结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    // 是否选中了任何内容
+    pub matched: bool,
+    // 出错的文件数，这些错误已经打印到了标准错误
+    pub errors: usize,
+    // 是否使用了-q
+    pub quiet: bool,
+}
+
+impl Outcome {
+    // 和grep一样：出过错时是2，否则有匹配时是0，没有匹配时是1。
+    // 例外是-q：只要有匹配就是0，即使其他文件出过错
+    pub fn exit_code(&self) -> i32 {
+        match (self.errors, self.matched) {
+            (_, true) if self.quiet => 0,
+            (0, true) => 0,
+            (0, false) => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_and_chain() {
+        let err = MinigrepError::from_io(Path::new("a.txt"), io::Error::new(io::ErrorKind::InvalidData, "bad byte"));
+        assert!(matches!(err, MinigrepError::Decode { .. }));
+        assert_eq!("a.txt: cannot decode contents", err.to_string());
+        assert_eq!("bad byte", err.source().unwrap().to_string());
+        assert_eq!("a.txt: cannot decode contents: bad byte", err.report().to_string());
+
+        let err = MinigrepError::from_io(Path::new("b.txt"), io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(err, MinigrepError::Io { path: Some(_), .. }));
+        assert_eq!("b.txt", err.to_string());
+        assert!(err.report().to_string().starts_with("b.txt: "));
+        assert!(!err.is_broken_pipe());
+        assert!(MinigrepError::from(io::Error::from(io::ErrorKind::BrokenPipe)).is_broken_pipe());
+
+        let config = crate::tests::parse(&["minigrep", "-E", "(", "f"]);
+        let err = MinigrepError::from(config.err().unwrap());
+        assert!(matches!(err, MinigrepError::InvalidPattern { ref pattern, .. } if pattern == "("));
+        let err = MinigrepError::from(ConfigError::NotEnoughArgs);
+        assert!(matches!(err.source().unwrap().downcast_ref(), Some(ConfigError::NotEnoughArgs)));
+        assert_eq!(2, err.exit_code());
+    }
+
+    #[test]
+    fn exit_codes() {
+        let outcome = |matched, errors, quiet| Outcome { matched, errors, quiet }.exit_code();
+        assert_eq!(0, outcome(true, 0, false));
+        assert_eq!(1, outcome(false, 0, false));
+        assert_eq!(2, outcome(true, 1, false));
+        assert_eq!(0, outcome(true, 1, true));
+        assert_eq!(2, outcome(false, 1, true));
+    }
+}
@@ -0,0 +1,471 @@
+// 结构化输入的按字段搜索：查询只在选中的那一列（--csv/--tsv --field）或者JSON路径（--jsonl --path）中匹配，
+// 打印的则是整条记录。
+// • CSV：字段用逗号分隔，可以用双引号括起来，引号中的""表示一个引号，引号中的换行不会结束这条记录
+// • TSV：字段用制表符分隔，没有引号，每行就是一条记录
+// • --field NAME按第一行（表头）中的列名查找，表头本身不会被选中；--field N是从1开始的列号，第一行也是数据
+// • JSON Lines：每行一个JSON值，--path .a.b依次取对象的键，数字也可以是数组的下标，比如.items.0.name。
+//   字符串按其内容匹配，其他值按JSON文本匹配
+// 没有这个字段的记录（列数不够、JSON格式错误或者路径不存在）不算匹配，-v会选中它们。
+// -c统计、-m限制的是记录数。
+
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::slice;
+
+use serde_json::Value;
+
+use crate::multiline::covered_lines;
+use crate::output::Printer;
+use crate::{selection_limit, split_lines, stream, Config, Match, OutputMode, Pattern};
+
+// --field的值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Name(String),
+    // 从0开始
+    Index(usize),
+}
+
+impl Field {
+    // 正整数是列号，其他都是列名
+    pub fn parse(value: &str) -> Option<Field> {
+        match value.parse::<usize>() {
+            Ok(0) => None,
+            Ok(n) => Some(Field::Index(n - 1)),
+            Err(_) if value.is_empty() => None,
+            Err(_) => Some(Field::Name(value.to_string())),
+        }
+    }
+}
+
+// 在记录中的什么地方匹配
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fields {
+    // --csv/--tsv --field
+    Delimited { delimiter: u8, quoted: bool, field: Field },
+    // --jsonl --path，"."是整个值
+    Json(Vec<String>),
+}
+
+impl Fields {
+    pub fn csv(field: Field) -> Fields {
+        Fields::Delimited {
+            delimiter: b',',
+            quoted: true,
+            field,
+        }
+    }
+
+    pub fn tsv(field: Field) -> Fields {
+        Fields::Delimited {
+            delimiter: b'\t',
+            quoted: false,
+            field,
+        }
+    }
+
+    // 解析--path的值，必须以'.'开头，不能有空的部分
+    pub fn json(path: &str) -> Option<Fields> {
+        let rest = path.strip_prefix('.')?;
+        if rest.is_empty() {
+            return Some(Fields::Json(Vec::new()));
+        }
+        let keys: Vec<String> = rest.split('.').map(String::from).collect();
+        if keys.iter().any(String::is_empty) {
+            return None;
+        }
+        Some(Fields::Json(keys))
+    }
+}
+
+// 一条记录
+struct Record<'a> {
+    // 这条记录包含的行在lines中的下标
+    lines: Range<usize>,
+    // 选中的字段的值，没有这个字段时为None
+    value: Option<Cow<'a, str>>,
+    // 值在整个内容中的起始位置；值和原文不一致（比如含有转义的引号）时为None，这时不高亮
+    offset: Option<usize>,
+    // --field NAME时的表头
+    header: bool,
+}
+
+// 从一条记录的文本中取出选中的字段，--field NAME时第一条记录是表头，从中找出列号
+struct Splitter<'f> {
+    fields: &'f Fields,
+    column: Option<usize>,
+    header: Option<&'f str>,
+}
+
+impl<'f> Splitter<'f> {
+    fn new(fields: &'f Fields) -> Splitter<'f> {
+        let (column, header) = match fields {
+            Fields::Delimited {
+                field: Field::Index(index),
+                ..
+            } => (Some(*index), None),
+            Fields::Delimited {
+                field: Field::Name(name),
+                ..
+            } => (None, Some(name.as_str())),
+            Fields::Json(_) => (None, None),
+        };
+        Splitter { fields, column, header }
+    }
+
+    // text是记录包含的各行，从内容中的first处开始
+    fn record<'a>(&mut self, text: &'a str, first: usize, lines: Range<usize>) -> io::Result<Record<'a>> {
+        let (delimiter, quoted) = match self.fields {
+            Fields::Json(keys) => {
+                let value = serde_json::from_str::<Value>(text)
+                    .ok()
+                    .and_then(|value| lookup(&value, keys).map(text_of));
+                return Ok(Record {
+                    lines,
+                    value: value.map(Cow::Owned),
+                    offset: None,
+                    header: false,
+                });
+            }
+            Fields::Delimited { delimiter, quoted, .. } => (*delimiter, *quoted),
+        };
+        let split = split_fields(text, delimiter, quoted);
+        if let Some(name) = self.header.take() {
+            let found = split.iter().position(|(_, value)| value == name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("no field named '{}' in the header", name),
+                )
+            })?;
+            self.column = Some(found);
+            return Ok(Record {
+                lines,
+                value: None,
+                offset: None,
+                header: true,
+            });
+        }
+        let (value, offset) = match self.column.and_then(|column| split.into_iter().nth(column)) {
+            Some((raw, value)) => {
+                let offset = (text[raw.clone()] == *value).then_some(first + raw.start);
+                (Some(value), offset)
+            }
+            None => (None, None),
+        };
+        Ok(Record {
+            lines,
+            value,
+            offset,
+            header: false,
+        })
+    }
+}
+
+// 只有带引号的CSV需要向后看：引号中的换行不会结束这条记录
+fn multiline_records(fields: &Fields) -> bool {
+    matches!(fields, Fields::Delimited { quoted: true, .. })
+}
+
+// 把内容切分为记录，并取出每条记录中选中的字段
+fn records<'a>(fields: &Fields, contents: &'a str, lines: &[Match<'a>]) -> io::Result<Vec<Record<'a>>> {
+    let mut splitter = Splitter::new(fields);
+    let mut records = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        // 引号没有配对时，这条记录延续到下一行
+        let mut end = start + 1;
+        let mut open = multiline_records(fields) && odd_quotes(lines[start].line);
+        while open && end < lines.len() {
+            open = !odd_quotes(lines[end].line);
+            end += 1;
+        }
+        let first = lines[start].byte_range.start;
+        let text = &contents[first..lines[end - 1].byte_range.end];
+        records.push(splitter.record(text, first, start..end)?);
+        start = end;
+    }
+    Ok(records)
+}
+
+// 引号的个数是奇数时，这一行结束时引号的开闭状态会改变
+fn odd_quotes(line: &str) -> bool {
+    line.bytes().filter(|b| *b == b'"').count() % 2 == 1
+}
+
+// 返回每个字段去掉引号之后在text中的范围，以及去掉转义之后的值
+fn split_fields(text: &str, delimiter: u8, quoted: bool) -> Vec<(Range<usize>, Cow<'_, str>)> {
+    let bytes = text.as_bytes();
+    let find = |from: usize, byte: u8| bytes[from..].iter().position(|b| *b == byte).map(|i| from + i);
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    loop {
+        let next = if quoted && bytes.get(pos) == Some(&b'"') {
+            let start = pos + 1;
+            let mut value = String::new();
+            let mut at = start;
+            let end = loop {
+                match find(at, b'"') {
+                    // ""是一个转义的引号
+                    Some(quote) if bytes.get(quote + 1) == Some(&b'"') => {
+                        value.push_str(&text[at..=quote]);
+                        at = quote + 2;
+                    }
+                    Some(quote) => {
+                        value.push_str(&text[at..quote]);
+                        at = quote + 1;
+                        break quote;
+                    }
+                    // 没有结束的引号，一直到记录末尾
+                    None => {
+                        value.push_str(&text[at..]);
+                        at = text.len();
+                        break text.len();
+                    }
+                }
+            };
+            fields.push((start..end, Cow::Owned(value)));
+            find(at, delimiter)
+        } else {
+            let next = find(pos, delimiter);
+            let end = next.unwrap_or(text.len());
+            fields.push((pos..end, Cow::Borrowed(&text[pos..end])));
+            next
+        };
+        match next {
+            Some(delimiter) => pos = delimiter + 1,
+            None => return fields,
+        }
+    }
+}
+
+fn lookup<'v>(value: &'v Value, keys: &[String]) -> Option<&'v Value> {
+    keys.iter().try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// 返回所有选中字段匹配的完整记录，多行的记录不含最后的换行符
+pub fn search_fields<'a>(pattern: &Pattern, fields: &Fields, contents: &'a str) -> io::Result<Vec<&'a str>> {
+    let lines = split_lines(contents);
+    Ok(records(fields, contents, &lines)?
+        .into_iter()
+        .filter(|record| record.value.as_deref().is_some_and(|value| pattern.is_match(value)))
+        .map(|record| &contents[lines[record.lines.start].byte_range.start..lines[record.lines.end - 1].byte_range.end])
+        .collect())
+}
+
+// search_input的按字段版本，返回被选中的记录数。
+// 每行一条记录的格式逐行读取，和普通搜索一样不需要把整个输入读入内存
+pub(crate) fn search_input<R: BufRead, W: Write>(
+    config: &Config,
+    fields: &Fields,
+    printer: &mut Printer,
+    out: &mut W,
+    path: &Path,
+    mut reader: R,
+) -> io::Result<usize> {
+    printer.begin_file();
+    let mut selection = Selection {
+        config,
+        print_lines: config.output == OutputMode::Lines && !config.quiet,
+        highlight: (printer.colors.is_some() || printer.json) && !config.invert,
+        limit: selection_limit(config),
+        count: 0,
+        printer,
+        out,
+        path,
+    };
+    if multiline_records(fields) {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        let lines = split_lines(&contents);
+        for record in records(fields, &contents, &lines)? {
+            let lines = &lines[record.lines.clone()];
+            if !selection.record(&record, lines, |spans| covered_lines(&contents, lines, spans))? {
+                break;
+            }
+        }
+    } else {
+        let mut splitter = Splitter::new(fields);
+        stream::for_each_line(reader, |line| {
+            // 值的位置相对于这一行
+            let record = splitter.record(line.line, 0, 0..1)?;
+            selection.record(&record, slice::from_ref(line), |spans| vec![Some(spans.to_vec())])
+        })?;
+    }
+    let Selection {
+        printer,
+        out,
+        print_lines,
+        count,
+        ..
+    } = selection;
+    if print_lines {
+        printer.end_file(out, path, count)?;
+    }
+    Ok(count)
+}
+
+// 逐条打印记录，统计选中的记录数
+struct Selection<'a, W> {
+    config: &'a Config,
+    printer: &'a mut Printer,
+    out: &'a mut W,
+    path: &'a Path,
+    print_lines: bool,
+    highlight: bool,
+    limit: Option<usize>,
+    count: usize,
+}
+
+impl<W: Write> Selection<'_, W> {
+    // lines是记录包含的行，covered把值中匹配的部分映射回这些行；返回false时停止搜索
+    fn record<F>(&mut self, record: &Record, lines: &[Match], covered: F) -> io::Result<bool>
+    where
+        F: FnOnce(&[Range<usize>]) -> Vec<Option<Vec<Range<usize>>>>,
+    {
+        if self.limit.is_some_and(|max| self.count >= max) {
+            if !(self.print_lines && self.printer.in_after_context()) {
+                return Ok(false);
+            }
+            for line in lines {
+                self.printer.line(self.out, self.path, line, false, &[])?;
+            }
+            return Ok(true);
+        }
+        let matched = record
+            .value
+            .as_deref()
+            .is_some_and(|value| self.config.pattern.is_match(value));
+        let selected = !record.header && matched != self.config.invert;
+        if selected {
+            self.count += 1;
+        }
+        if !self.print_lines {
+            return Ok(true);
+        }
+        let spans = match (&record.value, record.offset) {
+            (Some(value), Some(offset)) if selected && self.highlight => {
+                let spans: Vec<Range<usize>> = self
+                    .config
+                    .pattern
+                    .find_spans(value)
+                    .into_iter()
+                    .map(|span| offset + span.start..offset + span.end)
+                    .collect();
+                covered(&spans)
+            }
+            _ => vec![None; lines.len()],
+        };
+        for (line, spans) in lines.iter().zip(spans) {
+            self.printer
+                .line(self.out, self.path, line, selected, &spans.unwrap_or_default())?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn csv_fields() {
+        let split = split_fields(r#"1,"Smith, ""Jo""",x"#, b',', true);
+        let values: Vec<&str> = split.iter().map(|(_, value)| value.as_ref()).collect();
+        assert_eq!(vec!["1", r#"Smith, "Jo""#, "x"], values);
+        assert_eq!(
+            vec![0..1, 3..16, 18..19],
+            split.iter().map(|(raw, _)| raw.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(2, split_fields("a\t\"b", b'\t', false).len());
+        assert_eq!(Some(Field::Index(1)), Field::parse("2"));
+        assert_eq!(Some(Field::Name(String::from("city"))), Field::parse("city"));
+        assert_eq!(None, Field::parse("0"));
+    }
+
+    #[test]
+    fn search_by_column() {
+        let contents = "\
+id,name,city
+1,Oslo Smith,Bergen
+2,\"Bergen\nNorth\",Oslo
+3,Ann,Oslo";
+        let name = Fields::csv(Field::parse("name").unwrap());
+        let city = Fields::csv(Field::parse("3").unwrap());
+        let pattern = Pattern::new("oslo", false);
+        assert_eq!(
+            vec!["1,Oslo Smith,Bergen"],
+            search_fields(&pattern, &name, contents).unwrap()
+        );
+        // 引号中的换行属于同一条记录
+        assert_eq!(
+            vec!["2,\"Bergen\nNorth\",Oslo", "3,Ann,Oslo"],
+            search_fields(&pattern, &city, contents).unwrap()
+        );
+        let missing = Fields::csv(Field::parse("zip").unwrap());
+        assert!(search_fields(&pattern, &missing, contents).is_err());
+
+        let tsv = Fields::tsv(Field::Index(0));
+        assert_eq!(
+            vec!["b\ta"],
+            search_fields(&Pattern::new("b", true), &tsv, "a\tb\nb\ta").unwrap()
+        );
+    }
+
+    #[test]
+    fn search_by_json_path() {
+        let contents = r#"{"user":{"name":"ann"},"tags":["x","rust"],"n":42}
+{"user":{"name":"rusty"},"tags":[]}
+not json"#;
+        let pattern = Pattern::new("rust", true);
+        let name = Fields::json(".user.name").unwrap();
+        let tag = Fields::json(".tags.1").unwrap();
+        assert_eq!(1, search_fields(&pattern, &name, contents).unwrap().len());
+        assert!(search_fields(&pattern, &tag, contents).unwrap()[0].starts_with(r#"{"user":{"name":"ann"}"#));
+        assert_eq!(
+            1,
+            search_fields(&Pattern::new("42", true), &Fields::json(".n").unwrap(), contents)
+                .unwrap()
+                .len()
+        );
+        assert_eq!(None, Fields::json("user"));
+        assert_eq!(None, Fields::json(".a..b"));
+        assert_eq!(Some(Fields::Json(Vec::new())), Fields::json("."));
+    }
+
+    #[test]
+    fn one_record_per_line_streams() {
+        let config = |flags: &[&str]| {
+            let mut args = vec!["minigrep"];
+            args.extend_from_slice(flags);
+            crate::tests::parse(&args).unwrap()
+        };
+        let search = |config: &Config, input: &[u8]| {
+            let mut out = Vec::new();
+            // 输入永远不会结束，只有逐行读取时-m才能让搜索停下来
+            let reader = io::BufReader::new(input.chain(io::repeat(b'\n')));
+            let fields = config.fields.as_ref().unwrap();
+            let printer = &mut Printer::new(false, true, false, 0, 0);
+            let count = search_input(config, fields, printer, &mut out, Path::new("-"), reader).unwrap();
+            (count, String::from_utf8(out).unwrap())
+        };
+        let jsonl = config(&["--jsonl", "--path", ".user", "-m1", "ann"]);
+        let input = b"{\"user\":\"bob\"}\n{\"user\":\"ann\"}\n";
+        assert_eq!((1, String::from("2:{\"user\":\"ann\"}\n")), search(&jsonl, input));
+        let tsv = config(&["--tsv", "--field", "name", "-m1", "ann"]);
+        assert_eq!((1, String::from("3:2\tann\n")), search(&tsv, b"id\tname\n1\tbob\n2\tann\n"));
+    }
+}
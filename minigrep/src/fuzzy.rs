@@ -0,0 +1,115 @@
+// --fuzzy K：近似匹配，找出和query的编辑距离（Levenshtein距离）不超过K的子串。
+// 使用Sellers算法：和计算两个字符串编辑距离的动态规划相同，
+// 只是匹配可以从行中的任意位置开始，所以第0行全部为0。
+// 每个单元格同时记录这条路径从行中哪个字符开始，找到结果后就能得到匹配的范围。
+
+use std::ops::Range;
+
+use crate::pattern::{fold_case, Folded};
+
+pub struct Fuzzy {
+    // 不区分大小写时保存的是折叠过大小写的query
+    query: Vec<char>,
+    max_distance: usize,
+    case_sensitive: bool,
+}
+
+// 一个近似匹配：编辑距离以及在行中的字节范围
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub distance: usize,
+    pub span: Range<usize>,
+}
+
+impl Fuzzy {
+    pub fn new(query: &str, max_distance: usize, case_sensitive: bool) -> Fuzzy {
+        let query = if case_sensitive {
+            query.chars().collect()
+        } else {
+            fold_case(query).chars().collect()
+        };
+        Fuzzy {
+            query,
+            max_distance,
+            case_sensitive,
+        }
+    }
+
+    // 返回line中编辑距离最小的匹配，距离相同时返回最靠前的那个
+    pub fn find(&self, line: &str) -> Option<FuzzyMatch> {
+        if self.case_sensitive {
+            let (distance, span) = self.find_in(line)?;
+            return Some(FuzzyMatch { distance, span });
+        }
+        let folded = Folded::new(line);
+        let (distance, span) = self.find_in(&folded.text)?;
+        Some(FuzzyMatch {
+            distance,
+            span: folded.unfold(span),
+        })
+    }
+
+    fn find_in(&self, text: &str) -> Option<(usize, Range<usize>)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let byte_at = |index: usize| chars.get(index).map_or(text.len(), |(at, _)| *at);
+        let m = self.query.len();
+
+        // column[i]是query的前i个字符和“以当前字符结尾的某个子串”之间的最小距离，以及该子串的起始字符下标
+        let mut column: Vec<(usize, usize)> = (0..=m).map(|i| (i, 0)).collect();
+        let mut best = (column[m].0 <= self.max_distance).then_some((column[m].0, 0, 0));
+
+        for (j, (_, c)) in chars.iter().enumerate() {
+            let mut next = Vec::with_capacity(m + 1);
+            next.push((0, j + 1));
+            for i in 1..=m {
+                let (diag, diag_start) = column[i - 1];
+                let substitute = (diag + usize::from(self.query[i - 1] != *c), diag_start);
+                let skip_text = (column[i].0 + 1, column[i].1);
+                let skip_query = (next[i - 1].0 + 1, next[i - 1].1);
+                // 距离相同时优先选择替换（或相等），匹配的范围更紧凑
+                let cell = [skip_text, skip_query]
+                    .into_iter()
+                    .fold(substitute, |min, cell| if cell.0 < min.0 { cell } else { min });
+                next.push(cell);
+            }
+            column = next;
+
+            let (distance, start) = column[m];
+            if distance <= self.max_distance && best.is_none_or(|(d, _, _)| distance < d) {
+                best = Some((distance, start, j + 1));
+            }
+        }
+        best.map(|(distance, start, end)| (distance, byte_at(start)..byte_at(end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approximate_matches() {
+        let fuzzy = Fuzzy::new("productive", 2, true);
+        assert_eq!(
+            Some(FuzzyMatch { distance: 0, span: 12..22 }),
+            fuzzy.find("safe, fast, productive.")
+        );
+        // 两个字母颠倒，相当于两次替换
+        assert_eq!(
+            Some(FuzzyMatch { distance: 2, span: 4..14 }),
+            fuzzy.find("so, prodcutive!")
+        );
+        assert_eq!(None, fuzzy.find("product"));
+
+        // 距离更小的匹配优先于更靠前的匹配
+        let fuzzy = Fuzzy::new("body", 1, true);
+        assert_eq!(Some(FuzzyMatch { distance: 0, span: 7..11 }), fuzzy.find("a bdy, body"));
+    }
+
+    #[test]
+    fn case_insensitive_spans() {
+        let fuzzy = Fuzzy::new("strasse", 1, false);
+        assert_eq!(Some(FuzzyMatch { distance: 0, span: 0..7 }), fuzzy.find("STRAßE"));
+        assert_eq!(Some(FuzzyMatch { distance: 1, span: 4..12 }), fuzzy.find("Die Straaße"));
+    }
+}
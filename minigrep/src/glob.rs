@@ -0,0 +1,153 @@
+// 一个够用的glob实现，用于--include/--exclude过滤。
+// 支持的语法：
+// • *   匹配任意数量的字符，但不跨越'/'
+// • **  匹配任意数量的字符，可以跨越'/'
+// • ?   匹配单个字符（不含'/'）
+// • [abc]、[a-z]、[!abc] 字符集合
+// 模式中如果不含'/'，就只和文件名比较（比如*.rs）；否则和相对路径比较（比如src/**/*.rs）。
+// 以'/'开头的模式（比如/target）总是和相对路径比较，只匹配最顶层的条目。
+// 以'/'结尾的模式（比如target/）只匹配目录。
+
+#[derive(Clone)]
+pub struct Glob {
+    pattern: Vec<char>,
+    match_basename: bool,
+    dir_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+        let anchored = pattern.starts_with('/');
+        let pattern = pattern.trim_start_matches('/');
+        let match_basename = !anchored && !pattern.contains('/');
+        Glob {
+            pattern: pattern.chars().collect(),
+            match_basename,
+            dir_only,
+        }
+    }
+
+    // rel_path是相对于搜索根目录的路径，统一使用'/'分隔
+    pub fn is_match(&self, rel_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let subject = if self.match_basename {
+            rel_path.rsplit('/').next().unwrap_or(rel_path)
+        } else {
+            rel_path
+        };
+        let subject: Vec<char> = subject.chars().collect();
+        match_from(&self.pattern, &subject)
+    }
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            if pattern.get(1) == Some(&'*') {
+                // "**/"也可以匹配零层目录，比如src/**/*.rs能匹配src/main.rs
+                let rest = &pattern[2..];
+                if let Some(after_slash) = rest.strip_prefix(&['/']) {
+                    if match_from(after_slash, text) {
+                        return true;
+                    }
+                }
+                (0..=text.len()).any(|i| match_from(rest, &text[i..]))
+            } else {
+                let rest = &pattern[1..];
+                for i in 0..=text.len() {
+                    if match_from(rest, &text[i..]) {
+                        return true;
+                    }
+                    if i < text.len() && text[i] == '/' {
+                        break;
+                    }
+                }
+                false
+            }
+        }
+        Some('?') => match text.first() {
+            Some(c) if *c != '/' => match_from(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some('[') => match (text.first(), parse_class(pattern)) {
+            (Some(c), Some((matched, len))) if *c != '/' => {
+                matched(*c) && match_from(&pattern[len..], &text[1..])
+            }
+            // 没有闭合的'['按普通字符处理
+            (Some(c), None) => *c == '[' && match_from(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(p) => match text.first() {
+            Some(c) if c == p => match_from(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+    }
+}
+
+// 解析从'['开始的字符集合，返回判断函数以及整个集合在模式中占用的长度
+fn parse_class(pattern: &[char]) -> Option<(impl Fn(char) -> bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *pattern.get(i)?;
+        if c == ']' && !first {
+            break;
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|e| *e != ']') {
+            ranges.push((c, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+    let matched = move |c: char| ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi) != negated;
+    Some((matched, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basename_patterns() {
+        let glob = Glob::new("*.rs");
+        assert!(glob.is_match("main.rs", false));
+        assert!(glob.is_match("src/deep/lib.rs", false));
+        assert!(!glob.is_match("src/lib.rs.bak", false));
+        assert!(Glob::new("poem.tx?").is_match("poem.txt", false));
+        assert!(Glob::new("[a-c]*.txt").is_match("b.txt", false));
+        assert!(!Glob::new("[!a-c]*.txt").is_match("b.txt", false));
+    }
+
+    #[test]
+    fn path_patterns() {
+        let glob = Glob::new("src/**/*.rs");
+        assert!(glob.is_match("src/main.rs", false));
+        assert!(glob.is_match("src/a/b/c.rs", false));
+        assert!(!glob.is_match("tests/main.rs", false));
+        assert!(!Glob::new("src/*.rs").is_match("src/a/b.rs", false));
+        assert!(Glob::new("/target").is_match("target", true));
+        assert!(!Glob::new("/target").is_match("sub/target", true));
+    }
+
+    #[test]
+    fn dir_only_patterns() {
+        let glob = Glob::new("target/");
+        assert!(glob.is_match("target", true));
+        assert!(glob.is_match("sub/target", true));
+        assert!(!glob.is_match("target", false));
+    }
+}
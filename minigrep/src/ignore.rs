@@ -0,0 +1,200 @@
+// .gitignore、.ignore以及git全局排除文件的解析和匹配。
+// 每个文件中的规则都是相对于该文件所在目录的，语法和git相同：
+// • 空行和以'#'开头的行被忽略，以'!'开头的规则表示重新包含之前被排除的路径
+// • 以'/'结尾的规则只匹配目录，以'/'开头或者中间含有'/'的规则相对于文件所在目录
+// • 同一个文件中后出现的规则优先；越深的目录中的文件越优先，同一目录下.ignore优先于.gitignore
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+
+#[derive(Clone)]
+struct Rule {
+    glob: Glob,
+    // 以'!'开头的规则
+    whitelist: bool,
+}
+
+#[derive(Clone)]
+pub struct IgnoreFile {
+    rules: Vec<Rule>,
+    // 文件所在目录相对于搜索根目录的路径，只有这个目录下的路径才受这些规则约束
+    base: String,
+    // 文件位于搜索根目录之外（比如仓库根目录）时，从文件所在目录到搜索根目录的路径，以'/'结尾
+    prefix: String,
+}
+
+impl IgnoreFile {
+    pub fn parse(contents: &str, base: &str, prefix: &str) -> IgnoreFile {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            // 行尾的空格没有意义，除非用'\'转义
+            let line = if line.ends_with("\\ ") { line } else { line.trim_end() };
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (whitelist, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            // "\#"、"\!"表示以这两个字符开头的文件名
+            let pattern = pattern.strip_prefix('\\').unwrap_or(pattern);
+            rules.push(Rule {
+                glob: Glob::new(pattern),
+                whitelist,
+            });
+        }
+        IgnoreFile {
+            rules,
+            base: base.to_string(),
+            prefix: prefix.to_string(),
+        }
+    }
+
+    // 文件不存在、无法读取或者没有任何规则时返回None
+    pub fn load(path: &Path, base: &str, prefix: &str) -> Option<IgnoreFile> {
+        let contents = fs::read_to_string(path).ok()?;
+        let file = IgnoreFile::parse(&contents, base, prefix);
+        (!file.rules.is_empty()).then_some(file)
+    }
+
+    // rel_path相对于搜索根目录。Some(true)表示被排除，Some(false)表示被重新包含，None表示没有规则匹配
+    pub fn matched(&self, rel_path: &str, is_dir: bool) -> Option<bool> {
+        let rel_path = if self.base.is_empty() {
+            rel_path
+        } else {
+            rel_path.strip_prefix(&self.base)?.strip_prefix('/')?
+        };
+        let rel_path = format!("{}{}", self.prefix, rel_path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.glob.is_match(&rel_path, is_dir))
+            .map(|rule| !rule.whitelist)
+    }
+}
+
+// 按顺序检查一组规则文件，后面的文件优先
+pub fn is_ignored(files: &[IgnoreFile], rel_path: &str, is_dir: bool) -> bool {
+    files
+        .iter()
+        .rev()
+        .find_map(|file| file.matched(rel_path, is_dir))
+        .unwrap_or(false)
+}
+
+// 加载dir中的规则文件，按照优先级从低到高的顺序追加到files，base是dir相对于搜索根目录的路径
+pub fn load_dir(dir: &Path, base: &str, prefix: &str, files: &mut Vec<IgnoreFile>) {
+    for name in [".git/info/exclude", ".gitignore", ".ignore"] {
+        files.extend(IgnoreFile::load(&dir.join(name), base, prefix));
+    }
+}
+
+// 搜索根目录之外、直到git仓库根目录为止的各级父目录中的规则文件。
+// 不在git仓库中时不会向上查找，免得用上和这次搜索毫无关系的规则。
+pub fn load_parents(root: &Path, files: &mut Vec<IgnoreFile>) {
+    let root = match root.canonicalize() {
+        Ok(root) => root,
+        Err(_) => return,
+    };
+    let parents: Vec<&Path> = root.ancestors().skip(1).collect();
+    if root.join(".git").exists() {
+        return;
+    }
+    let repo = match parents.iter().position(|dir| dir.join(".git").exists()) {
+        Some(repo) => repo,
+        None => return,
+    };
+    // 从仓库根目录开始，越往下越优先
+    for dir in parents[..=repo].iter().rev() {
+        let prefix: Vec<String> = root
+            .strip_prefix(dir)
+            .unwrap_or(&root)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        load_dir(dir, "", &format!("{}/", prefix.join("/")), files);
+    }
+}
+
+// git的全局排除文件：core.excludesFile，没有设置时是$XDG_CONFIG_HOME/git/ignore
+pub fn global_excludes() -> Option<IgnoreFile> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let configured = home
+        .as_ref()
+        .and_then(|home| fs::read_to_string(home.join(".gitconfig")).ok())
+        .and_then(|config| excludes_file(&config))
+        .map(|path| match (path.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path),
+        });
+    let path = configured.or_else(|| {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".config")))?;
+        Some(config_home.join("git").join("ignore"))
+    })?;
+    IgnoreFile::load(&path, "", "")
+}
+
+// 从.gitconfig的[core]段中找出excludesFile的值
+fn excludes_file(config: &str) -> Option<String> {
+    let mut in_core = false;
+    for line in config.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_core = line.eq_ignore_ascii_case("[core]");
+        } else if let (true, Some((key, value))) = (in_core, line.split_once('=')) {
+            if key.trim().eq_ignore_ascii_case("excludesfile") {
+                return Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_and_negation() {
+        let file = IgnoreFile::parse("# build output\ntarget/\n*.log\n!keep.log\n/TODO\n\\#notes\n", "", "");
+        assert_eq!(Some(true), file.matched("target", true));
+        assert_eq!(Some(true), file.matched("sub/target", true));
+        assert_eq!(None, file.matched("target", false));
+        assert_eq!(Some(true), file.matched("logs/a.log", false));
+        assert_eq!(Some(false), file.matched("logs/keep.log", false));
+        assert_eq!(Some(true), file.matched("TODO", false));
+        assert_eq!(None, file.matched("src/TODO", false));
+        assert_eq!(Some(true), file.matched("#notes", false));
+    }
+
+    #[test]
+    fn nested_files() {
+        let files = vec![
+            IgnoreFile::parse("*.txt\n", "", ""),
+            IgnoreFile::parse("!poem.txt\n/out\n", "docs", ""),
+        ];
+        assert!(is_ignored(&files, "notes.txt", false));
+        assert!(is_ignored(&files, "docs/notes.txt", false));
+        assert!(!is_ignored(&files, "docs/poem.txt", false));
+        assert!(is_ignored(&files, "poem.txt", false));
+        assert!(is_ignored(&files, "docs/out", true));
+        assert!(!is_ignored(&files, "out", true));
+
+        // 搜索根目录是仓库中的src，仓库根目录中的/src/gen只匹配src下的gen
+        let files = vec![IgnoreFile::parse("/src/gen\n", "", "src/")];
+        assert!(is_ignored(&files, "gen", true));
+        assert!(!is_ignored(&files, "a/gen", true));
+    }
+
+    #[test]
+    fn gitconfig_excludes_file() {
+        let config = "[user]\n\tname = x\n[core]\n\teditor = vim\n\texcludesFile = \"~/.gitignore_global\"\n";
+        assert_eq!(Some(String::from("~/.gitignore_global")), excludes_file(config));
+        assert_eq!(None, excludes_file("[user]\n\texcludesfile = x\n"));
+    }
+}
@@ -0,0 +1,346 @@
+// 三元组（trigram）索引：`minigrep index build <DIR>`把DIR中每个文件出现过的所有三字节序列
+// 记录在DIR/.minigrep-index中，之后`minigrep --index <DIR> <QUERY>`只搜索可能包含QUERY的文件。
+// • 索引的是折叠过大小写的内容，区分和不区分大小写的搜索可以共用同一个索引
+// • 只有字面量模式（包括-e/-f给出的多个字面量）才能用来筛选，正则表达式和--fuzzy仍然搜索所有文件，
+//   -v和-L关心的是不匹配的内容，-c要为每个文件打印行数，同样不筛选
+// • 再次build时，修改时间和大小都没有变化的文件直接沿用上次的结果；
+//   搜索时，索引之后被修改或新增的文件总是会被搜索，所以过期的索引只会变慢，不会漏掉结果
+//
+// 文件格式（整数都是小端序）：
+//   "MGIDX1\n"、文件数u32，然后是每个文件：
+//   路径长度u32、相对于DIR的路径（UTF-8）、修改时间的秒数u64和纳秒数u32、大小u64、
+//   三元组个数u32、排好序的三元组（每个u32，低24位是三个字节）
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::pattern::fold_case;
+use crate::{encoding, ignore, walk, Config, OutputMode, Pattern};
+
+pub const INDEX_FILE: &str = ".minigrep-index";
+const MAGIC: &[u8] = b"MGIDX1\n";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    // 无法获取修改时间时为None，这样的文件总是被当作已经修改过
+    modified: Option<(u64, u32)>,
+    size: u64,
+    // 二进制文件不会被搜索，它的三元组为空
+    trigrams: Vec<u32>,
+}
+
+impl Entry {
+    fn is_fresh(&self, metadata: &fs::Metadata) -> bool {
+        self.modified.is_some() && self.modified == modified(metadata) && self.size == metadata.len()
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    // 键是相对于索引目录的路径，用'/'分隔
+    files: BTreeMap<String, Entry>,
+}
+
+// 一次build的结果，用于打印摘要
+#[derive(Debug, PartialEq)]
+pub struct BuildStats {
+    pub files: usize,
+    pub updated: usize,
+    pub removed: usize,
+    // 无法读取、因而没有被索引的文件和目录数。它们不在索引中，搜索时总是会被搜索
+    pub errors: usize,
+}
+
+impl Index {
+    // 读取dir中的索引，索引不存在或格式不对时返回错误，错误信息中不含索引文件的路径
+    pub fn load(dir: &Path) -> io::Result<Index> {
+        let file = File::open(dir.join(INDEX_FILE)).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("{} (run 'minigrep index build {}' first)", err, dir.display()),
+            )
+        })?;
+        Index::read(BufReader::new(file))
+    }
+
+    // 建立或者增量更新dir中的索引。
+    // 和搜索时一样，单个文件或目录的错误交给on_error，跳过它继续建立索引；只有写索引失败才返回Err
+    pub fn build<F: FnMut(&Path, io::Error)>(dir: &Path, mut on_error: F) -> io::Result<BuildStats> {
+        let mut walk_errors = 0;
+        let mut filter = walk::Filter::new(&[], &[]);
+        filter.global_excludes = ignore::global_excludes();
+        let files = walk::walk_paths(&[dir.display().to_string()], &filter, |path, err| {
+            walk_errors += 1;
+            on_error(path, err);
+        });
+        let mut stats = Index::update(dir, files, on_error)?;
+        stats.errors += walk_errors;
+        Ok(stats)
+    }
+
+    // build的后半部分：为files建立索引，写入dir
+    fn update<F: FnMut(&Path, io::Error)>(dir: &Path, files: Vec<PathBuf>, mut on_error: F) -> io::Result<BuildStats> {
+        let old = Index::load(dir).unwrap_or_default();
+        let root = dir.canonicalize()?;
+        let mut index = Index::default();
+        let (mut updated, mut errors) = (0, 0);
+        for path in files {
+            // 遍历之后才被删除的文件在这里就找不到了，它本来就不需要被索引
+            let key = match relative_key(&root, &path) {
+                Some(key) => key,
+                None => continue,
+            };
+            let entry = fs::metadata(&path).and_then(|metadata| match old.files.get(&key) {
+                Some(entry) if entry.is_fresh(&metadata) => Ok((entry.clone(), false)),
+                _ => {
+                    let entry = Entry {
+                        modified: modified(&metadata),
+                        size: metadata.len(),
+                        trigrams: file_trigrams(&path)?,
+                    };
+                    Ok((entry, true))
+                }
+            });
+            match entry {
+                Ok((entry, changed)) => {
+                    updated += usize::from(changed);
+                    index.files.insert(key, entry);
+                }
+                Err(err) => {
+                    errors += 1;
+                    on_error(&path, err);
+                }
+            }
+        }
+        let removed = old.files.keys().filter(|key| !index.files.contains_key(*key)).count();
+
+        // 先写进临时文件再改名，写到一半失败时不会留下损坏的索引
+        let path = dir.join(INDEX_FILE);
+        let tmp = dir.join(format!("{}.tmp", INDEX_FILE));
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        index.write(&mut out)?;
+        out.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(BuildStats {
+            files: index.files.len(),
+            updated,
+            removed,
+            errors,
+        })
+    }
+
+    // 从files中去掉肯定不会有匹配的文件，dir是索引所在的目录
+    pub fn candidates(&self, config: &Config, dir: &Path, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        // -c要为没有匹配的文件打印0，所以同样不筛选。
+        // CSV和JSON中的值可能经过了转义，原文中不一定能找到查询的三元组
+        if config.invert
+            || config.fields.is_some()
+            || matches!(config.output, OutputMode::Count | OutputMode::FilesWithoutMatch)
+        {
+            return files;
+        }
+        let alternatives = match required_trigrams(&config.pattern, &config.patterns) {
+            Some(alternatives) => alternatives,
+            None => return files,
+        };
+        let root = match dir.canonicalize() {
+            Ok(root) => root,
+            Err(_) => return files,
+        };
+        files
+            .into_iter()
+            .filter(|path| {
+                let entry = relative_key(&root, path).and_then(|key| self.files.get(&key));
+                match (entry, fs::metadata(path)) {
+                    (Some(entry), Ok(metadata)) if entry.is_fresh(&metadata) => alternatives
+                        .iter()
+                        .any(|required| required.iter().all(|t| entry.trigrams.binary_search(t).is_ok())),
+                    // 不在索引中或者索引之后被修改过
+                    _ => true,
+                }
+            })
+            .collect()
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.files.len() as u32).to_le_bytes())?;
+        for (key, entry) in &self.files {
+            out.write_all(&(key.len() as u32).to_le_bytes())?;
+            out.write_all(key.as_bytes())?;
+            let (secs, nanos) = entry.modified.unwrap_or((u64::MAX, u32::MAX));
+            out.write_all(&secs.to_le_bytes())?;
+            out.write_all(&nanos.to_le_bytes())?;
+            out.write_all(&entry.size.to_le_bytes())?;
+            out.write_all(&(entry.trigrams.len() as u32).to_le_bytes())?;
+            for trigram in &entry.trigrams {
+                out.write_all(&trigram.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read<R: Read>(mut input: R) -> io::Result<Index> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a minigrep index"));
+        }
+        let mut index = Index::default();
+        for _ in 0..read_u32(&mut input)? {
+            let mut key = vec![0; read_u32(&mut input)? as usize];
+            input.read_exact(&mut key)?;
+            let key = String::from_utf8(key).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            let secs = read_u64(&mut input)?;
+            let nanos = read_u32(&mut input)?;
+            let size = read_u64(&mut input)?;
+            let trigrams = (0..read_u32(&mut input)?)
+                .map(|_| read_u32(&mut input))
+                .collect::<io::Result<Vec<_>>>()?;
+            let modified = (secs != u64::MAX).then_some((secs, nanos));
+            index.files.insert(key, Entry { modified, size, trigrams });
+        }
+        Ok(index)
+    }
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn modified(metadata: &fs::Metadata) -> Option<(u64, u32)> {
+    let since = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since.as_secs(), since.subsec_nanos()))
+}
+
+// root是规范化之后的索引目录，path同样先规范化，"docs/a"、"./docs/a"和绝对路径都能对应到同一个索引项
+fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let rel = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("/"))
+}
+
+// 按照搜索时的方式读取文件：根据BOM解码，非法的UTF-8被替换
+fn file_trigrams(path: &Path) -> io::Result<Vec<u32>> {
+    let mut reader = encoding::maybe_decode(BufReader::new(File::open(path)?), None, true)?;
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    if walk::is_binary(contents.as_bytes()) {
+        return Ok(Vec::new());
+    }
+    Ok(trigrams(&fold_case(&contents)))
+}
+
+// 排好序、去掉重复的所有三元组
+fn trigrams(text: &str) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = text
+        .as_bytes()
+        .windows(3)
+        .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+// 匹配的文件必须包含其中某一组中的全部三元组，每个模式对应一组。
+// 无法从模式中得出必须出现的三元组时（正则表达式、近似匹配、短于三个字节的模式）返回None。
+fn required_trigrams(pattern: &Pattern, patterns: &[String]) -> Option<Vec<Vec<u32>>> {
+    match pattern {
+        Pattern::Word(inner) => required_trigrams(inner, patterns),
+        Pattern::Literal(_)
+        | Pattern::CaseInsensitive(_)
+        | Pattern::Literals(_)
+        | Pattern::CaseInsensitiveLiterals(_) => patterns
+            .iter()
+            .map(|p| Some(trigrams(&fold_case(p))).filter(|t| !t.is_empty()))
+            .collect(),
+        Pattern::Regex(_) | Pattern::Fuzzy(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_trigrams_of_patterns() {
+        let pattern = Pattern::new("Duct", false);
+        let required = required_trigrams(&pattern, &[String::from("Duct")]).unwrap();
+        assert_eq!(vec![trigrams("duct")], required);
+        assert_eq!(None, required_trigrams(&Pattern::new("ab", true), &[String::from("ab")]));
+        let re = Pattern::Regex(regex::Regex::new("abc").unwrap());
+        assert_eq!(None, required_trigrams(&re, &[String::from("abc")]));
+    }
+
+    #[test]
+    fn build_and_narrow() {
+        let dir = std::env::temp_dir().join(format!("minigrep-index-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "safe, fast, productive.\n").unwrap();
+        fs::write(dir.join("sub/b.txt"), "Duct tape.\n").unwrap();
+        fs::write(dir.join("c.bin"), "duct\0tape").unwrap();
+
+        let stats = Index::build(&dir, |path, err| panic!("{}: {}", path.display(), err)).unwrap();
+        assert_eq!(BuildStats { files: 3, updated: 3, removed: 0, errors: 0 }, stats);
+        let index = Index::load(&dir).unwrap();
+        let mut written = Vec::new();
+        index.write(&mut written).unwrap();
+        assert_eq!(index, Index::read(&written[..]).unwrap());
+
+        let root = dir.display().to_string();
+        let files = walk::walk_paths(std::slice::from_ref(&root), &walk::Filter::new(&[], &[]), |path, err| {
+            panic!("{}: {}", path.display(), err)
+        });
+        let narrow = |flags: &[&str]| -> Vec<String> {
+            let mut list = vec!["minigrep", "--index", &root];
+            list.extend_from_slice(flags);
+            let config = crate::tests::parse(&list).unwrap();
+            index
+                .candidates(&config, &dir, files.clone())
+                .iter()
+                .map(|path| relative_key(&dir.canonicalize().unwrap(), path).unwrap())
+                .collect()
+        };
+        // 二进制文件c.bin中的tape不算
+        assert_eq!(vec!["sub/b.txt"], narrow(&["-i", "TAPE"]));
+        assert!(narrow(&["slow"]).is_empty());
+        assert_eq!(vec!["a.txt", "sub/b.txt"], narrow(&["-e", "fast", "-e", "tape"]));
+        // 正则表达式、-v和-c不筛选
+        assert_eq!(3, narrow(&["-E", "sl.w"]).len());
+        assert_eq!(3, narrow(&["-v", "slow"]).len());
+        assert_eq!(3, narrow(&["-c", "slow"]).len());
+
+        // 修改过的文件在重新build之前总是会被搜索，重新build时只更新它
+        fs::write(dir.join("a.txt"), "slow, slower than before\n").unwrap();
+        assert_eq!(vec!["a.txt"], narrow(&["slow"]));
+        fs::remove_file(dir.join("c.bin")).unwrap();
+        let stats = Index::build(&dir, |path, err| panic!("{}: {}", path.display(), err)).unwrap();
+        assert_eq!(BuildStats { files: 2, updated: 1, removed: 1, errors: 0 }, stats);
+
+        // 无法读取的文件被报告并跳过，遍历之后才被删除的文件直接忽略，索引照常写入
+        let mut failed = Vec::new();
+        let files = vec![dir.join("a.txt"), dir.join("gone.txt"), dir.join("sub")];
+        let stats = Index::update(&dir, files, |path, _| failed.push(path.to_path_buf())).unwrap();
+        assert_eq!(BuildStats { files: 1, updated: 0, removed: 1, errors: 1 }, stats);
+        assert_eq!(vec![dir.join("sub")], failed);
+        assert_eq!(1, Index::load(&dir).unwrap().files.len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
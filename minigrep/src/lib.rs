@@ -5,14 +5,148 @@
 // • Config的定义
 // • Config::new函数的定义
 
-use std::error::Error;
-use std::fs;
+use std::fs::File;
 use std::env;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::iter;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+use encoding_rs::Encoding;
+use regex::{Regex, RegexBuilder};
+
+mod cli;
+mod config_file;
+mod decompress;
+mod encoding;
+mod error;
+mod fields;
+mod fuzzy;
+mod glob;
+mod ignore;
+mod index;
+mod multiline;
+mod output;
+mod parallel;
+mod pattern;
+mod replace;
+mod searcher;
+mod stream;
+mod types;
+mod watch;
+mod walk;
+
+use cli::Arg;
+use config_file::FileConfig;
+pub use cli::{ConfigError, USAGE};
+pub use error::{MinigrepError, Outcome, Report};
+pub use fields::{search_fields, Field, Fields};
+pub use fuzzy::{Fuzzy, FuzzyMatch};
+pub use index::{BuildStats, Index};
+pub use multiline::search_multiline;
+pub use output::Colors;
+pub use pattern::Pattern;
+pub use searcher::{ContextKind, Matcher, Searcher, SearcherBuilder, Sink};
+pub use stream::{for_each_line, search_reader, MatchBuf, SearchReader};
+pub use types::Types;
+
+// 路径"-"表示标准输入
+pub const STDIN_PATH: &str = "-";
 
 pub struct Config {
+    // 使用-e/-f给出多个模式时，query是用换行符连接起来的所有模式
     pub query: String,
-    pub filename: String,
-    pub case_sensitive: bool
+    // 所有模式，任意一个匹配即可
+    pub patterns: Vec<String>,
+    // 可以是多个文件或目录，目录会被递归搜索
+    pub paths: Vec<String>,
+    pub case_sensitive: bool,
+    // -w：只匹配完整的单词
+    pub whole_word: bool,
+    // --fuzzy K：允许的最大编辑距离，None表示精确匹配
+    pub fuzzy: Option<usize>,
+    // --rank：把每个文件中的近似匹配按距离从小到大排列后再打印
+    pub rank: bool,
+    // -U：模式可以跨越多行，匹配覆盖到的所有行都会被打印
+    pub multiline: bool,
+    // --csv/--tsv --field、--jsonl --path：只在记录的某个字段中匹配，打印整条记录
+    pub fields: Option<Fields>,
+    // 由query编译而来：使用-E/--regex时，query会在Config::new中被编译为正则表达式，之后的每一行都复用这一个Regex
+    pub pattern: Pattern,
+    // 遍历目录时用来筛选文件的glob，比如--include '*.rs'、--exclude 'target/'
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // --hidden：搜索隐藏文件；--no-ignore：不遵守.gitignore等规则，同时也搜索隐藏文件
+    pub hidden: bool,
+    pub no_ignore: bool,
+    // --index DIR：用DIR中由`minigrep index build`建立的索引排除不可能匹配的文件
+    pub index: Option<String>,
+    // -n：打印行号；-b：打印该行在文件中的字节偏移
+    pub line_number: bool,
+    pub byte_offset: bool,
+    // -z：自动识别并解压gzip、zstd、bzip2格式的输入
+    pub decompress: bool,
+    // --encoding：没有BOM的输入使用的编码，None表示UTF-8
+    pub encoding: Option<&'static Encoding>,
+    // --lossy：把无法解码的字节替换为U+FFFD，而不是报错
+    pub lossy: bool,
+    // -B/-A/-C：匹配行之前、之后打印多少行上下文
+    pub before_context: usize,
+    pub after_context: usize,
+    // -v：选中不匹配的行
+    pub invert: bool,
+    pub output: OutputMode,
+    // -m：每个文件最多选中多少行
+    pub max_count: Option<usize>,
+    // -q：什么都不打印，只通过退出码告诉调用者有没有匹配
+    pub quiet: bool,
+    pub color: ColorChoice,
+    // 配置文件中的配色方案
+    pub colors: Colors,
+    // 所有文件类型的定义：内置类型、配置文件[types]中的类型以及--type-add添加的类型。
+    // -t/-T选中的类型已经展开到include/exclude中了
+    pub types: Types,
+    // --type-list：打印所有文件类型后退出
+    pub type_list: bool,
+    // -j：同时搜索文件的线程数，0表示使用CPU核数
+    pub threads: usize,
+    // --json：以JSON Lines格式输出，方便其他工具解析
+    pub json: bool,
+    // --replace：把匹配到的文本替换后再打印，正则模式下可以用$1、${name}引用捕获组
+    pub replace: Option<String>,
+    // --in-place[=SUFFIX]：直接修改文件，给出SUFFIX时先把原文件备份为“文件名+SUFFIX”
+    pub in_place: bool,
+    pub backup_suffix: Option<String>,
+    // --dry-run：配合--in-place使用，只打印将要进行的修改
+    pub dry_run: bool,
+    // --watch：一直运行，文件有变化时重新搜索
+    pub watch: bool,
+    // --follow：像tail -f一样只搜索新追加到文件末尾的行
+    pub follow: bool,
+}
+
+// --color=auto|always|never
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    // 只有标准输出是终端时才上色
+    Auto,
+    Always,
+    Never,
+}
+
+// 对每个文件的搜索结果如何输出
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    // 默认：打印选中的行
+    Lines,
+    // -c：打印每个文件选中的行数
+    Count,
+    // -l：只打印有匹配的文件名
+    FilesWithMatches,
+    // -L：只打印没有匹配的文件名
+    FilesWithoutMatch,
 }
 
 impl Config {
@@ -25,48 +159,884 @@ impl Config {
     // 为了给它赋值，我们调用了env::var函数，
     // 并将环境变量CASE_INSENSITIVE的名称作为参数传递给该函数。
     // env::var函数会返回一个Result作为结果，只有在环境变量被设置时，该结果才会是包含环境变量值的Ok变体，而在环境变量未被设置时，该结果则会是一个Err变体。
-    pub fn new(args: &[String]) -> Result<Config, &'static str> {
+    pub fn new(args: &[String]) -> Result<Config, ConfigError> {
 
         // 我们可以返回一个Result值，它会在成功的情况下包含Config实例，并在失败的情况下携带具体的问题描述。
        // 当我们在main函数中调用Config::new时，就可以使用Result类型来表明当前是否存在问题。
        // 接着，我们还可以在main函数中将可能出现的Err变体转换为一种更加友好的形式来通知用户。
        // 使用这种方法可以避免调用panic! 时在错误提示信息前后产生thread 'main'和RUST_BACKTRACE等内部信息。
-        if args.len() < 3 {
-            return Err("not enough 参数");
+        // --no-config出现在"--"之前时完全不读取配置文件
+        let no_config = args.iter().skip(1).take_while(|arg| *arg != "--").any(|arg| arg == "--no-config");
+        let file = if no_config {
+            FileConfig::default()
+        } else {
+            config_file::load()?
+        };
+        // 如果CASE_INSENSITIVE环境变量被设置为了某个值，那么is_ok就会返回真，
+        // 也就意味着程序会进行不区分大小写的搜索。因为我们不关心环境变量的具体值，
+        // 只关心其存在与否，所以我们直接使用了is_ok而不是unwrap、expect或其他曾经接触过的Result的方法。
+        Config::from_layers(args, file, env::var("CASE_INSENSITIVE").is_ok())
+    }
+
+    // 由已经读取好的配置文件和环境变量构造Config，不再访问文件系统和环境变量，测试中也用它来隔离开发者自己的配置
+    pub(crate) fn from_layers(
+        args: &[String],
+        file: FileConfig,
+        case_insensitive: bool,
+    ) -> Result<Config, ConfigError> {
+        // 选项可以出现在任意位置，剩下的参数依旧按照query、path...的顺序解析
+        let mut use_regex = false;
+        // -e和-f给出的模式，按出现的顺序排列
+        let mut patterns = Vec::new();
+        let mut explicit_patterns = false;
+        // None表示配置文件、环境变量和命令行都没有指定，默认区分大小写
+        let mut ignore_case: Option<bool> = None;
+        // -S：query中没有大写字母时才忽略大小写
+        let mut smart_case = false;
+        let mut whole_word = false;
+        let mut fuzzy = None;
+        let mut rank = false;
+        let mut multiline = false;
+        // --csv、--tsv或--jsonl
+        let mut format = None;
+        let mut field = None;
+        let mut json_path = None;
+        let mut include = Vec::new();
+        // 配置文件中的ignore和--exclude作用相同
+        let mut exclude = file.ignore;
+        // -t/-T给出的类型名，在所有--type-add处理完之后才展开为glob
+        let mut select_types = Vec::new();
+        let mut negate_types = Vec::new();
+        let mut types = Types::new();
+        for (name, globs) in &file.types {
+            types.add(name, globs);
         }
+        let mut type_list = false;
+        let mut hidden = false;
+        let mut no_ignore = false;
+        let mut index = None;
+        let mut line_number = false;
+        let mut byte_offset = false;
+        let mut decompress = false;
+        let mut encoding = None;
+        let mut lossy = false;
+        let mut before_context = 0;
+        let mut after_context = 0;
+        let mut invert = false;
+        let mut output = OutputMode::Lines;
+        let mut max_count = None;
+        let mut quiet = false;
+        let mut color = ColorChoice::Auto;
+        let mut threads = 0;
+        let mut json = false;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut backup_suffix = None;
+        let mut dry_run = false;
+        let mut watch = false;
+        let mut follow = false;
+        let mut positional = Vec::new();
 
+        // 配置文件中的选项最先处理，环境变量其次，命令行最后。
+        // 同一个选项后出现的覆盖先出现的，这样就得到了“配置文件 < 环境变量 < 命令行”的优先级。
+        // 每一层单独切分，配置文件中的"-e"这样缺少值的选项不会吃掉命令行的参数
+        let mut parse_layer = |args: &[String]| -> Result<(), ConfigError> {
+            let mut parser = cli::Parser::new(args);
+            while let Some(arg) = parser.next_arg()? {
+                let flag = match arg {
+                    Arg::Value(value) => {
+                        positional.push(value);
+                        continue;
+                    }
+                    Arg::Flag(flag) => flag,
+                };
+                match flag.as_str() {
+                    "-E" | "--regex" => use_regex = true,
+                    "-e" | "--regexp" => {
+                        patterns.push(parser.value()?);
+                        explicit_patterns = true;
+                    }
+                    "-f" | "--file" => {
+                        patterns.extend(read_patterns(&parser.value()?)?);
+                        explicit_patterns = true;
+                    }
+                    // -i、-s、-S中以最后出现的为准
+                    "-i" | "--ignore-case" => {
+                        ignore_case = Some(true);
+                        smart_case = false;
+                    }
+                    "-s" | "--case-sensitive" => {
+                        ignore_case = Some(false);
+                        smart_case = false;
+                    }
+                    "-S" | "--smart-case" => {
+                        ignore_case = None;
+                        smart_case = true;
+                    }
+                    "-w" | "--word-regexp" => whole_word = true,
+                    "--fuzzy" => fuzzy = Some(parser.number()?),
+                    "--rank" => rank = true,
+                    "-U" | "--multiline" => multiline = true,
+                    "--csv" | "--tsv" | "--jsonl" => format = Some(flag),
+                    "--field" => {
+                        let value = parser.value()?;
+                        field = Some(Field::parse(&value).ok_or(ConfigError::InvalidValue { flag, value })?);
+                    }
+                    "--path" => {
+                        let value = parser.value()?;
+                        json_path = Some(Fields::json(&value).ok_or(ConfigError::InvalidValue { flag, value })?);
+                    }
+                    "-n" | "--line-number" => line_number = true,
+                    "-b" | "--byte-offset" => byte_offset = true,
+                    "-z" | "--decompress" => decompress = true,
+                    "--encoding" => {
+                        let value = parser.value()?;
+                        encoding = encoding::parse(&value).ok_or(ConfigError::InvalidValue { flag, value })?;
+                    }
+                    "--lossy" => lossy = true,
+                    "-A" | "--after-context" => after_context = parser.number()?,
+                    "-B" | "--before-context" => before_context = parser.number()?,
+                    "-C" | "--context" => {
+                        after_context = parser.number()?;
+                        before_context = after_context;
+                    }
+                    "-v" | "--invert-match" => invert = true,
+                    "-c" | "--count" => output = OutputMode::Count,
+                    "-l" | "--files-with-matches" => output = OutputMode::FilesWithMatches,
+                    "-L" | "--files-without-match" => output = OutputMode::FilesWithoutMatch,
+                    "-m" | "--max-count" => max_count = Some(parser.number()?),
+                    "-q" | "--quiet" | "--silent" => quiet = true,
+                    // 和grep一样，单独的--color等价于--color=auto
+                    "--color" | "--colour" => {
+                        color = match parser.optional_value().as_deref() {
+                            None | Some("auto") => ColorChoice::Auto,
+                            Some("always") => ColorChoice::Always,
+                            Some("never") => ColorChoice::Never,
+                            Some(value) => {
+                                return Err(ConfigError::InvalidValue {
+                                    flag: flag.clone(),
+                                    value: value.to_string(),
+                                })
+                            }
+                        }
+                    }
+                    "-j" | "--threads" => threads = parser.number()?,
+                    "--json" => json = true,
+                    "--replace" => replace = Some(parser.value()?),
+                    "--in-place" => {
+                        in_place = true;
+                        backup_suffix = parser.optional_value();
+                    }
+                    "--dry-run" => dry_run = true,
+                    "--watch" => watch = true,
+                    "--follow" => follow = true,
+                    "--include" => include.push(parser.value()?),
+                    "--exclude" => exclude.push(parser.value()?),
+                    "-t" | "--type" => select_types.push(parser.value()?),
+                    "-T" | "--type-not" => negate_types.push(parser.value()?),
+                    "--type-add" => {
+                        let value = parser.value()?;
+                        if types.add_spec(&value).is_none() {
+                            return Err(ConfigError::InvalidValue { flag, value });
+                        }
+                    }
+                    "--type-list" => type_list = true,
+                    "--hidden" => hidden = true,
+                    "--no-ignore" => no_ignore = true,
+                    "--index" => index = Some(parser.value()?),
+                    // 已经在最开始处理过了
+                    "--no-config" => {}
+                    "-h" | "--help" => return Err(ConfigError::HelpRequested),
+                    "-V" | "--version" => return Err(ConfigError::VersionRequested),
+                    _ => return Err(ConfigError::UnknownFlag(parser.last_flag().to_string())),
+                }
+            }
+            Ok(())
+        };
+        let program = args.iter().take(1).cloned();
+        if !file.flags.is_empty() {
+            let flags: Vec<String> = program.clone().chain(file.flags).collect();
+            parse_layer(&flags).map_err(|err| ConfigError::ConfigFile {
+                path: file.path.clone(),
+                message: err.to_string(),
+            })?;
+        }
+        if case_insensitive {
+            parse_layer(&program.chain(iter::once(String::from("--ignore-case"))).collect::<Vec<_>>())?;
+        }
+        parse_layer(args)?;
 
-        let query = args[1].clone();
-        let filename = args[2].clone();
-        // 如果CASE_INSENSITIVE环境变量被设置为了某个值，那么is_err就会返回假，
-        // 也就意味着程序会进行不区分大小写的搜索。因为我们不关心环境变量的具体值，
-        // 只关心其存在与否，所以我们直接使用了is_err而不是unwrap、expect或其他曾经接触过的Result的方法。
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-        Ok(Config { query, filename, case_sensitive })
+        if positional.is_empty() && !explicit_patterns && !type_list {
+            return Err(ConfigError::NotEnoughArgs);
+        }
+        // -t的类型展开为--include，-T的类型展开为--exclude
+        expand_types(&types, "--type", &select_types, &mut include)?;
+        expand_types(&types, "--type-not", &negate_types, &mut exclude)?;
+        if json && output != OutputMode::Lines {
+            return Err(ConfigError::ConflictingFlags("--json", "-c/-l/-L"));
+        }
+        if in_place && replace.is_none() {
+            return Err(ConfigError::MissingFlag("--in-place", "--replace"));
+        }
+        if dry_run && !in_place {
+            return Err(ConfigError::MissingFlag("--dry-run", "--in-place"));
+        }
+        if in_place && (json || invert || output != OutputMode::Lines) {
+            return Err(ConfigError::ConflictingFlags("--in-place", "-v/-c/-l/-L/--json"));
+        }
+        // 替换后的内容无法再压缩、转码回原来的格式
+        if in_place && (decompress || encoding.is_some() || lossy) {
+            return Err(ConfigError::ConflictingFlags("--in-place", "-z/--encoding/--lossy"));
+        }
+        if (watch || follow) && in_place {
+            return Err(ConfigError::ConflictingFlags("--watch/--follow", "--in-place"));
+        }
+        // --follow逐行打印新增的匹配，没有“整个文件”的概念，也不会因为-q、-m而停下
+        if follow && (output != OutputMode::Lines || quiet || max_count.is_some() || decompress || rank) {
+            return Err(ConfigError::ConflictingFlags("--follow", "-c/-l/-L/-q/-m/-z/--rank"));
+        }
+        // 新增的内容按行单独解码，UTF-16这样的编码中换行符不是一个单独的字节
+        if follow && encoding.is_some_and(|encoding| !encoding.is_ascii_compatible()) {
+            return Err(ConfigError::ConflictingFlags("--follow", "--encoding=utf-16"));
+        }
+        if fuzzy.is_some() && (use_regex || patterns.len() > 1) {
+            return Err(ConfigError::ConflictingFlags("--fuzzy", "-E/-e/-f"));
+        }
+        if rank && fuzzy.is_none() {
+            return Err(ConfigError::MissingFlag("--rank", "--fuzzy"));
+        }
+        // 排序之后行的先后顺序被打乱，上下文没有意义
+        if rank && (invert || before_context > 0 || after_context > 0) {
+            return Err(ConfigError::ConflictingFlags("--rank", "-v/-A/-B/-C"));
+        }
+        let fields = match format.as_deref() {
+            Some("--jsonl") => {
+                if field.is_some() {
+                    return Err(ConfigError::ConflictingFlags("--field", "--jsonl"));
+                }
+                Some(json_path.ok_or(ConfigError::MissingFlag("--jsonl", "--path"))?)
+            }
+            Some(format) => {
+                if json_path.is_some() {
+                    return Err(ConfigError::ConflictingFlags("--path", "--csv/--tsv"));
+                }
+                let field = field.ok_or(ConfigError::MissingFlag("--csv/--tsv", "--field"))?;
+                Some(if format == "--csv" { Fields::csv(field) } else { Fields::tsv(field) })
+            }
+            None if field.is_some() => return Err(ConfigError::MissingFlag("--field", "--csv/--tsv")),
+            None if json_path.is_some() => return Err(ConfigError::MissingFlag("--path", "--jsonl")),
+            None => None,
+        };
+        // 按字段搜索需要完整的记录，替换、近似匹配、-U和--follow都是以行为单位的
+        if fields.is_some() && (replace.is_some() || fuzzy.is_some() || multiline || follow) {
+            return Err(ConfigError::ConflictingFlags("--csv/--tsv/--jsonl", "--replace/--fuzzy/-U/--follow"));
+        }
+        // 索引是按照文件的原始内容（根据BOM解码）建立的
+        if index.is_some() && (decompress || encoding.is_some()) {
+            return Err(ConfigError::ConflictingFlags("--index", "-z/--encoding"));
+        }
+        // -U需要读完整个文件才能匹配；替换、近似匹配和--follow都是逐行进行的
+        if multiline && (replace.is_some() || fuzzy.is_some() || follow) {
+            return Err(ConfigError::ConflictingFlags("-U", "--replace/--fuzzy/--follow"));
+        }
+
+
+        // 没有-e/-f时，第一个位置参数就是唯一的模式
+        let mut paths = if explicit_patterns || positional.is_empty() {
+            positional
+        } else {
+            patterns.push(positional.remove(0));
+            positional
+        };
+        let query = patterns.join("\n");
+        // 距离不小于query的长度时，空串就是一个匹配，每一行都会被选中
+        if let Some(k) = fuzzy.filter(|k| *k >= query.chars().count()) {
+            return Err(ConfigError::InvalidValue {
+                flag: String::from("--fuzzy"),
+                value: k.to_string(),
+            });
+        }
+        // 没有给出路径时搜索--index的目录，否则从标准输入读取
+        if paths.is_empty() {
+            paths.push(index.clone().unwrap_or_else(|| String::from(STDIN_PATH)));
+        }
+        if in_place && paths.iter().any(|path| path == STDIN_PATH) {
+            return Err(ConfigError::ConflictingFlags("--in-place", "standard input"));
+        }
+        if (watch || follow) && paths.iter().any(|path| path == STDIN_PATH) {
+            return Err(ConfigError::ConflictingFlags("--watch/--follow", "standard input"));
+        }
+        let case_sensitive = match ignore_case {
+            Some(ignore_case) => !ignore_case,
+            None if smart_case => patterns.iter().any(|p| p.chars().any(char::is_uppercase)),
+            None => true,
+        };
+
+        // 非法的正则表达式在这里就以Err的形式返回，而不是等到搜索时再panic
+        let pattern = if let Some(k) = fuzzy {
+            Pattern::Fuzzy(Fuzzy::new(&query, k, case_sensitive))
+        } else if use_regex && !patterns.is_empty() {
+            // 多个正则表达式合并为一个分支，同样只需要扫描一遍
+            for p in &patterns {
+                RegexBuilder::new(p).build().map_err(|source| ConfigError::InvalidRegex {
+                    pattern: p.clone(),
+                    source,
+                })?;
+            }
+            let alternation: Vec<String> = patterns.iter().map(|p| format!("(?:{})", p)).collect();
+            // -U时^和$匹配每一行的开头和结尾（包括\r\n中\r之前的位置），而不只是整个文件的开头和结尾
+            let re = RegexBuilder::new(&alternation.join("|"))
+                .case_insensitive(!case_sensitive)
+                .multi_line(multiline)
+                .crlf(multiline)
+                .build()
+                .map_err(|source| ConfigError::InvalidRegex {
+                    pattern: query.clone(),
+                    source,
+                })?;
+            Pattern::Regex(re)
+        } else {
+            Pattern::literals(&patterns, case_sensitive).map_err(ConfigError::PatternSet)?
+        };
+        let pattern = if whole_word {
+            Pattern::Word(Box::new(pattern))
+        } else {
+            pattern
+        };
+
+        Ok(Config {
+            query,
+            patterns,
+            paths,
+            case_sensitive,
+            whole_word,
+            fuzzy,
+            rank,
+            multiline,
+            fields,
+            pattern,
+            include,
+            exclude,
+            hidden,
+            no_ignore,
+            index,
+            line_number,
+            byte_offset,
+            decompress,
+            encoding,
+            lossy,
+            before_context,
+            after_context,
+            invert,
+            output,
+            max_count,
+            quiet,
+            color,
+            colors: file.colors,
+            types,
+            type_list,
+            threads,
+            json,
+            replace,
+            in_place,
+            backup_suffix,
+            dry_run,
+            watch,
+            follow,
+        })
     }
 }
 
-// run的升级版
-// Box<dyn Error>意味着函数会返回一个实现了Error trait的类型，但我们并不需要指定具体的类型是什么。
-// 这意味着我们可以在不同的错误场景下返回不同的错误类型，语句中的dyn关键字所表达的正是这种“动态”（dynamic）的含义。
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.filename)?;
+fn expand_types(types: &Types, flag: &str, names: &[String], globs: &mut Vec<String>) -> Result<(), ConfigError> {
+    for name in names {
+        let found = types.globs(name).ok_or_else(|| ConfigError::InvalidValue {
+            flag: flag.to_string(),
+            value: name.clone(),
+        })?;
+        globs.extend(found.iter().cloned());
+    }
+    Ok(())
+}
 
-    let result = if config.case_sensitive {
-        search(&config.query, &contents)
+// 读取-f给出的模式文件，每行一个模式，"-"表示从标准输入读取
+fn read_patterns(path: &str) -> Result<Vec<String>, ConfigError> {
+    let contents = if path == STDIN_PATH {
+        io::read_to_string(io::stdin())
     } else {
-        search_case_insensitive(&config.query, &contents)
+        std::fs::read_to_string(path)
     };
+    let contents = contents.map_err(|source| ConfigError::PatternFile {
+        path: path.to_string(),
+        source,
+    })?;
+    Ok(split_lines(&contents).into_iter().map(|m| m.line.to_string()).collect())
+}
 
-    for line in result {
-        println!("{}", line);
+// run的升级版
+// 最初的run返回Box<dyn Error>，调用者只能拿到一段错误信息；现在返回具体的MinigrepError，可以按照错误的种类分别处理。
+// 单个文件的错误不会让run返回Err，它们被打印到标准错误并计入Outcome.errors，
+// main根据Outcome决定退出码（和grep一样，0表示有匹配，1表示没有，2表示出过错）。
+pub fn run(config: Config) -> Result<Outcome, MinigrepError> {
+    let mut outcome = Outcome {
+        matched: config.type_list,
+        errors: 0,
+        quiet: config.quiet,
+    };
+    let config = Arc::new(config);
+    let result = if config.type_list {
+        list_types(&config)
+    } else if config.follow {
+        watch::follow(&config)
+    } else if config.watch {
+        watch::watch(&config)
+    } else {
+        search_all(&config, &mut outcome)
+    };
+    match result {
+        // 标准输出被关闭（比如管道另一端的head已经退出）不是错误，和grep一样安静地结束。
+        // 正在打印的文件还没有计入outcome，不过打印匹配行或者-l的路径本身就说明有匹配
+        Err(err) if err.is_broken_pipe() => {
+            outcome.matched |= matches!(config.output, OutputMode::Lines | OutputMode::FilesWithMatches);
+            Ok(outcome)
+        }
+        result => result.map(|()| outcome),
     }
+}
 
+fn list_types(config: &Config) -> Result<(), MinigrepError> {
+    let mut out = io::stdout().lock();
+    config.types.list(&mut out)?;
+    out.flush()?;
     Ok(())
 }
 
+// minigrep index build [DIR]...：为每个目录建立或增量更新索引，没有给出目录时是当前目录。
+// 返回因为出错而跳过的文件数，这些错误已经打印到了标准错误
+pub fn build_indexes(dirs: &[String]) -> Result<usize, MinigrepError> {
+    let current = [String::from(".")];
+    let dirs = if dirs.is_empty() { &current[..] } else { dirs };
+    let mut errors = 0;
+    for dir in dirs {
+        let stats = Index::build(Path::new(dir), |path, err| {
+            eprintln!("minigrep: {}", MinigrepError::from_io(path, err).report());
+        })
+        .map_err(|source| MinigrepError::Io {
+            path: Some(PathBuf::from(dir)),
+            source,
+        })?;
+        println!(
+            "{}: indexed {} files ({} updated, {} removed, {} skipped)",
+            dir, stats.files, stats.updated, stats.removed, stats.errors
+        );
+        errors += stats.errors;
+    }
+    Ok(errors)
+}
+
+// 打印一个文件的错误，搜索继续进行
+fn report(err: &MinigrepError, errors: &mut usize) {
+    eprintln!("minigrep: {}", err.report());
+    *errors += 1;
+}
+
+// 按照config搜索一遍所有的路径，结果记录在outcome中
+fn search_all(config: &Arc<Config>, outcome: &mut Outcome) -> Result<(), MinigrepError> {
+    let mut files = walk::walk_paths(&config.paths, &file_filter(config), |path, source| {
+        report(&MinigrepError::Io { path: Some(path.to_path_buf()), source }, &mut outcome.errors);
+    });
+    let mut printer = make_printer(config, &files);
+    // 在决定是否打印路径之后再筛选，输出的格式不会因为使用了索引而改变
+    if let Some(dir) = &config.index {
+        let dir = Path::new(dir);
+        let index = Index::load(dir).map_err(|source| MinigrepError::Io {
+            path: Some(dir.join(index::INDEX_FILE)),
+            source,
+        })?;
+        files = index.candidates(config, dir, files);
+    }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    // 用于--json的summary记录
+    let mut stats = Stats::default();
+
+    // 原地替换会修改文件，按顺序逐个处理
+    if config.in_place {
+        for path in files {
+            let edited = File::open(&path)
+                .and_then(|file| Ok(walk::is_binary(BufReader::new(file).fill_buf()?)))
+                .and_then(|binary| if binary { Ok(0) } else { replace::edit_file(config, &path, &mut out) });
+            match edited {
+                Ok(count) => outcome.matched |= count > 0,
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Err(err.into()),
+                Err(err) => report(&MinigrepError::from_io(&path, err), &mut outcome.errors),
+            }
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    // 标准输入需要边读边输出，所以只有全部是普通文件时才并行搜索
+    let parallel = threads > 1
+        && files.len() > 1
+        && !files.iter().any(|path| path == Path::new(STDIN_PATH));
+
+    if parallel {
+        // printed：之前的文件有没有输出；file_printed：当前文件有没有输出
+        let (mut printed, mut file_printed) = (false, false);
+        for event in parallel::search_files(Arc::clone(config), &printer, files, threads) {
+            let result = match event {
+                parallel::Event::Output(chunk) => {
+                    // 每个线程都不知道前面的文件有没有输出，文件之间的"--"由这里补上
+                    if !file_printed {
+                        if printed && config.output == OutputMode::Lines {
+                            printer.file_separator(&mut out)?;
+                        }
+                        (printed, file_printed) = (true, true);
+                    }
+                    out.write_all(&chunk)?;
+                    continue;
+                }
+                parallel::Event::Done(result) => {
+                    file_printed = false;
+                    result
+                }
+            };
+            let result = match result {
+                Ok(result) => result,
+                Err(err) => {
+                    // 先把之前的输出写出去，错误信息才会出现在合适的位置
+                    out.flush()?;
+                    report(&err, &mut outcome.errors);
+                    continue;
+                }
+            };
+            stats.add(result);
+            outcome.matched |= result.is_some_and(|(selected, _)| selected);
+            if config.quiet && outcome.matched {
+                break;
+            }
+        }
+    } else {
+        for path in files {
+            let result = match search_path(config, &mut printer, &mut out, &path) {
+                Ok(result) => result,
+                // 标准输出被关闭（比如管道另一端的head已经退出），继续搜索没有意义
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Err(err.into()),
+                Err(err) => {
+                    out.flush()?;
+                    report(&MinigrepError::from_io(input_label(&path), err), &mut outcome.errors);
+                    continue;
+                }
+            };
+            stats.add(result);
+            outcome.matched |= result.is_some_and(|(selected, _)| selected);
+            if config.quiet && outcome.matched {
+                break;
+            }
+        }
+    }
+    printer.summary(&mut out, stats.searches, stats.searches_with_match, stats.matched_lines)?;
+    out.flush()?;
+    Ok(())
+}
+
+// 输出和错误信息中用来指代path的名称
+pub(crate) fn input_label(path: &Path) -> &Path {
+    if path == Path::new(STDIN_PATH) {
+        Path::new("(standard input)")
+    } else {
+        path
+    }
+}
+
+fn file_filter(config: &Config) -> walk::Filter {
+    let mut filter = walk::Filter::new(&config.include, &config.exclude);
+    filter.hidden = config.hidden || config.no_ignore;
+    filter.ignore_files = !config.no_ignore;
+    if filter.ignore_files {
+        filter.global_excludes = ignore::global_excludes();
+    }
+    filter
+}
+
+fn make_printer(config: &Config, files: &[PathBuf]) -> output::Printer {
+    // 和grep -r一样：只搜索单个文件时直接打印匹配行，否则在行首加上"路径:"
+    let with_path = files.len() > 1
+        || config.paths.len() > 1
+        || config.paths.iter().any(|p| Path::new(p).is_dir());
+
+    let mut printer = output::Printer::new(
+        with_path,
+        config.line_number,
+        config.byte_offset,
+        config.before_context,
+        config.after_context,
+    );
+    let colored = match config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal(),
+    };
+    if colored && !config.json {
+        printer.colors = Some(config.colors.clone());
+    }
+    printer.json = config.json;
+    printer
+}
+
+#[derive(Default)]
+struct Stats {
+    searches: usize,
+    searches_with_match: usize,
+    matched_lines: usize,
+}
+
+impl Stats {
+    fn add(&mut self, result: Option<(bool, usize)>) {
+        if let Some((_, count)) = result {
+            self.searches += 1;
+            self.matched_lines += count;
+            if count > 0 {
+                self.searches_with_match += 1;
+            }
+        }
+    }
+}
+
+// 搜索一个文件或标准输入，并按照输出模式打印结果。
+// 返回这个输入是否被选中（对-L来说是没有匹配）以及选中的行数，跳过的二进制文件返回None。
+fn search_path<W: Write>(
+    config: &Config,
+    printer: &mut output::Printer,
+    out: &mut W,
+    path: &Path,
+) -> io::Result<Option<(bool, usize)>> {
+    // 无论是文件还是标准输入都边读边搜索，内存中只保留当前行和-B要求的上下文
+    let is_stdin = path == Path::new(STDIN_PATH);
+    let label = input_label(path);
+    let reader: Box<dyn BufRead> = if is_stdin {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    let reader = if config.decompress {
+        decompress::maybe_decompress(reader)?
+    } else {
+        reader
+    };
+    let mut reader = encoding::maybe_decode(reader, config.encoding, config.lossy)?;
+    // 解压、转码之后再判断是不是二进制文件，UTF-16的内容中本来就有很多NUL字节
+    if !is_stdin && walk::is_binary(reader.fill_buf()?) {
+        return Ok(None);
+    }
+    let count = search_input(config, printer, out, label, reader)?;
+
+    // -L选中的是没有匹配的文件
+    let selected = match config.output {
+        OutputMode::FilesWithoutMatch => count == 0,
+        _ => count > 0,
+    };
+    if config.quiet {
+        return Ok(Some((selected, count)));
+    }
+
+    match config.output {
+        OutputMode::Lines => {}
+        OutputMode::Count => printer.count(out, label, count)?,
+        OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch => {
+            if selected {
+                printer.path(out, label)?;
+            }
+        }
+    }
+    Ok(Some((selected, count)))
+}
+
+// 搜索一个输入，返回其中被选中的行数
+fn search_input<R: BufRead, W: Write>(
+    config: &Config,
+    printer: &mut output::Printer,
+    out: &mut W,
+    path: &Path,
+    reader: R,
+) -> io::Result<usize> {
+    if config.multiline {
+        return multiline::search_input(config, printer, out, path, reader);
+    }
+    if let Some(fields) = &config.fields {
+        return fields::search_input(config, fields, printer, out, path, reader);
+    }
+    printer.begin_file();
+    let print_lines = config.output == OutputMode::Lines && !config.quiet;
+    // 不打印行时上下文没有意义；-v、-m和上下文都由Searcher处理，和嵌入使用时是同一份实现
+    let (before, after) = if print_lines {
+        (config.before_context, config.after_context)
+    } else {
+        (0, 0)
+    };
+    let searcher = Searcher::builder()
+        .before_context(before)
+        .after_context(after)
+        .invert(config.invert)
+        .max_count(selection_limit(config))
+        .build();
+    let mut sink = LineSink {
+        config,
+        print_lines,
+        lines: output::PrinterSink { printer, out, path },
+        ranked: Vec::new(),
+    };
+    let count = searcher.search_reader(&config.pattern, reader, &mut sink)?;
+    // 排序是稳定的，距离相同的行保持原来的顺序
+    let LineSink { mut lines, mut ranked, .. } = sink;
+    ranked.sort_by_key(|(fuzzy, _, _)| fuzzy.distance);
+    for (fuzzy, spans, line) in &ranked {
+        lines.fuzzy_matched(&line.as_match(), spans, fuzzy)?;
+    }
+    if print_lines {
+        lines.printer.end_file(lines.out, path, count)?;
+    }
+    Ok(count)
+}
+
+// 命令行搜索使用的Sink：在PrinterSink之上处理--replace、--fuzzy和--rank，-c/-l/-q时什么都不打印
+struct LineSink<'a, W> {
+    config: &'a Config,
+    print_lines: bool,
+    lines: output::PrinterSink<'a, W>,
+    // --rank时先收集所有近似匹配，读完之后再排序打印
+    ranked: Vec<(FuzzyMatch, Vec<Range<usize>>, MatchBuf)>,
+}
+
+impl<W: Write> Sink for LineSink<'_, W> {
+    fn matched(&mut self, line: &Match, spans: &[Range<usize>]) -> io::Result<bool> {
+        let config = self.config;
+        if !self.print_lines {
+            return Ok(true);
+        }
+        // -v选中的行本身没有匹配，不需要替换，也没有编辑距离
+        if config.invert {
+            return self.lines.matched(line, spans);
+        }
+        // --replace时打印替换后的内容，替换后的文本不再高亮
+        if let Some(replacement) = &config.replace {
+            let replaced = config.pattern.replace(line.line, replacement);
+            let line = Match {
+                line: &replaced,
+                ..line.clone()
+            };
+            return self.lines.matched(&line, &[]);
+        }
+        match config.fuzzy.and_then(|_| config.pattern.fuzzy_match(line.line)) {
+            Some(fuzzy) if config.rank => {
+                self.ranked.push((fuzzy, spans.to_vec(), MatchBuf::from(line)));
+                Ok(true)
+            }
+            Some(fuzzy) => self.lines.fuzzy_matched(line, spans, &fuzzy),
+            None => self.lines.matched(line, spans),
+        }
+    }
+
+    fn context(&mut self, line: &Match, kind: ContextKind) -> io::Result<bool> {
+        self.lines.context(line, kind)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.lines.context_break()
+    }
+
+    fn needs_spans(&self) -> bool {
+        self.print_lines && self.lines.needs_spans()
+    }
+}
+
+// 每个文件最多选中多少行。-l/-L/-q只关心有没有匹配，选中第一行之后就可以停下
+fn selection_limit(config: &Config) -> Option<usize> {
+    if config.quiet || !matches!(config.output, OutputMode::Lines | OutputMode::Count) {
+        Some(1)
+    } else {
+        config.max_count
+    }
+}
+
+// 一条搜索结果：行号从1开始，byte_range是这一行（不含换行符）在整个内容中的字节范围
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    pub line_number: usize,
+    pub byte_range: Range<usize>,
+    pub line: &'a str,
+}
+
+// 和str::lines一样按\n或\r\n切分，但同时记录每一行的行号和字节范围
+pub fn split_lines(contents: &str) -> Vec<Match<'_>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        lines.push(Match {
+            line_number: index + 1,
+            byte_range: start..start + line.len(),
+            line,
+        });
+        start += raw.len();
+    }
+    lines
+}
+
+// 所有搜索函数的公共部分：返回is_match判断为真的那些行
+pub fn find_matches<'a, F>(contents: &'a str, is_match: F) -> Vec<Match<'a>>
+where
+    F: Fn(&str) -> bool,
+{
+    split_lines(contents)
+        .into_iter()
+        .filter(|m| is_match(m.line))
+        .collect()
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // 用大小写折叠代替to_lowercase，"STRASSE"同样能找到"Straße"
+    let pattern = Pattern::new(query, false);
+    find_matches(contents, |line| pattern.is_match(line))
+        .into_iter()
+        .map(|m| m.line)
+        .collect()
+}
+
+
+
+
+// 近似匹配：返回包含和query的编辑距离不超过max_distance的子串的行
+pub fn search_fuzzy<'a>(query: &str, max_distance: usize, contents: &'a str) -> Vec<&'a str> {
+    let fuzzy = Fuzzy::new(query, max_distance, true);
+    find_matches(contents, |line| fuzzy.find(line).is_some())
+        .into_iter()
+        .map(|m| m.line)
+        .collect()
+}
+
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    find_matches(contents, |line| line.contains(query))
+        .into_iter()
+        .map(|m| m.line)
+        .collect()
+}
+
+// 正则模式：大小写是否敏感已经在编译Regex时决定，这里只需要逐行判断是否匹配
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<&'a str> {
+    find_matches(contents, |line| re.is_match(line))
+        .into_iter()
+        .map(|m| m.line)
+        .collect()
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     #[test]
@@ -118,34 +1088,381 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
-}
 
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
 
+    // 不读取开发者自己的配置文件和CASE_INSENSITIVE，其他模块的测试也用它来创建Config
+    pub(crate) fn parse(list: &[&str]) -> Result<Config, ConfigError> {
+        Config::from_layers(&args(list), FileConfig::default(), false)
+    }
 
+    #[test]
+    fn regex_search() {
+        let re = Regex::new(r"^(\d{2}):\d{2} (ERROR|WARN)").unwrap();
+        let contents = "\
+10:31 INFO started
+10:32 ERROR E1042 disk full
+10:33 WARN retrying
+at 10:34 ERROR not at start";
 
+        assert_eq!(
+            vec!["10:32 ERROR E1042 disk full", "10:33 WARN retrying"],
+            search_regex(&re, contents)
+        );
+    }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
+    #[test]
+    fn regex_flag_compiles_query() {
+        let config = parse(&["minigrep", "-E", "[a-z]+ody", "poem.txt"]).unwrap();
+        assert_eq!("[a-z]+ody", config.query);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert!(matches!(config.pattern, Pattern::Regex(_)));
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
-        }
+        let config = parse(&["minigrep", "body", "poem.txt"]).unwrap();
+        assert!(matches!(config.pattern, Pattern::Literal(_)));
     }
 
-    results
-}
+    #[test]
+    fn invalid_regex_is_an_error() {
+        let err = parse(&["minigrep", "--regex", "(unclosed", "poem.txt"])
+            .err()
+            .unwrap();
+        assert!(matches!(&err, ConfigError::InvalidRegex { pattern, .. } if pattern == "(unclosed"));
+        assert!(err.to_string().starts_with("invalid regex '(unclosed'"));
+    }
 
+    #[test]
+    fn multiple_paths_and_globs() {
+        let config = parse(&[
+            "minigrep", "--include", "*.rs", "fn", "src", "tests", "--exclude=target/",
+        ])
+        .unwrap();
+        assert_eq!("fn", config.query);
+        assert_eq!(vec!["src", "tests"], config.paths);
+        assert_eq!(vec!["*.rs"], config.include);
+        assert_eq!(vec!["target/"], config.exclude);
+        assert!(!config.hidden && !config.no_ignore);
 
+        let config = parse(&["minigrep", "--hidden", "--no-ignore", "fn", "src"]).unwrap();
+        assert!(config.hidden && config.no_ignore);
+    }
 
+    #[test]
+    fn match_records() {
+        let contents = "Rust:\r\nsafe, fast, productive.\nPick three.";
+        assert_eq!(
+            vec![Match {
+                line_number: 2,
+                byte_range: 7..30,
+                line: "safe, fast, productive.",
+            }],
+            find_matches(contents, |line| line.contains("duct"))
+        );
+    }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let mut results = Vec::new();
-    for line in contents.lines(){
-        if line.contains(query) {
-            results.push(line);
+    #[test]
+    fn context_flags() {
+        let config = parse(&["minigrep", "-n", "-b", "-C", "2", "-A", "1", "x", "f"]).unwrap();
+        assert!(config.line_number && config.byte_offset);
+        assert_eq!((2, 1), (config.before_context, config.after_context));
+
+        assert!(parse(&["minigrep", "-A", "many", "x", "f"]).is_err());
+
+        assert!(parse(&["minigrep", "-z", "x", "f.gz"]).unwrap().decompress);
+        assert!(matches!(
+            parse(&["minigrep", "-z", "--replace=y", "--in-place", "x", "f.gz"]),
+            Err(ConfigError::ConflictingFlags("--in-place", _))
+        ));
+        let config = parse(&["minigrep", "--encoding", "GBK", "--lossy", "x", "f"]).unwrap();
+        assert_eq!(Some(encoding_rs::GBK), config.encoding);
+        assert!(config.lossy);
+        assert!(matches!(
+            parse(&["minigrep", "--encoding=ebcdic", "x", "f"]),
+            Err(ConfigError::InvalidValue { value, .. }) if value == "ebcdic"
+        ));
+    }
+
+    #[test]
+    fn flag_parsing() {
+        let config = parse(&["minigrep", "-inC2", "--", "-x", "f"]).unwrap();
+        assert!(!config.case_sensitive);
+        assert!(config.line_number);
+        assert_eq!((2, 2), (config.before_context, config.after_context));
+        assert_eq!("-x", config.query);
+
+        assert!(matches!(
+            parse(&["minigrep", "-nY", "x", "f"]),
+            Err(ConfigError::UnknownFlag(flag)) if flag == "-Y"
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--help"]),
+            Err(ConfigError::HelpRequested)
+        ));
+        assert!(matches!(
+            parse(&["minigrep"]),
+            Err(ConfigError::NotEnoughArgs)
+        ));
+        assert_eq!("missing QUERY", ConfigError::NotEnoughArgs.to_string());
+    }
+
+    #[test]
+    fn no_config_flag() {
+        let config = Config::new(&args(&["minigrep", "--no-config", "x", "f"])).unwrap();
+        assert_eq!(vec!["f"], config.paths);
+        assert_eq!(Colors::default(), config.colors);
+        assert_eq!(None, config.types.globs("web"));
+        // "--"之后的--no-config是普通的值
+        let config = Config::new(&args(&["minigrep", "--no-config", "--", "--no-config"])).unwrap();
+        assert_eq!("--no-config", config.query);
+    }
+
+    #[test]
+    fn config_layers() {
+        let layered = |flags: &[&str], case_insensitive, list: &[&str]| {
+            let file = FileConfig {
+                flags: args(flags),
+                ignore: args(&["*.tmp"]),
+                ..FileConfig::default()
+            };
+            Config::from_layers(&args(list), file, case_insensitive).unwrap()
+        };
+
+        let config = layered(&["-s", "-n"], false, &["minigrep", "x", "f"]);
+        assert!(config.case_sensitive && config.line_number);
+        assert_eq!(vec!["*.tmp"], config.exclude);
+        // 配置文件 < CASE_INSENSITIVE < 命令行
+        assert!(!layered(&["-s"], true, &["minigrep", "x", "f"]).case_sensitive);
+        assert!(layered(&["-s"], true, &["minigrep", "-s", "x", "f"]).case_sensitive);
+        assert!(!layered(&["-i"], false, &["minigrep", "x", "f"]).case_sensitive);
+        assert!(layered(&["-i"], false, &["minigrep", "-s", "x", "f"]).case_sensitive);
+
+        // 配置文件中的错误指出是哪个文件，缺少值的选项也不会用掉命令行的参数
+        for flags in [&["--bogus"][..], &["-e"]] {
+            let file = FileConfig {
+                path: String::from("/etc/minigrep.toml"),
+                flags: args(flags),
+                ..FileConfig::default()
+            };
+            let err = Config::from_layers(&args(&["minigrep", "x", "f"]), file, false).err();
+            assert!(matches!(err, Some(ConfigError::ConfigFile { ref path, .. }) if path == "/etc/minigrep.toml"));
         }
     }
-    results
-}
\ No newline at end of file
+
+    #[test]
+    fn type_flags() {
+        let config = parse(&[
+            "minigrep", "-t", "rust", "-T", "web", "--type-add=web:*.html,*.css", "--include", "*.in", "fn", "src",
+        ])
+        .unwrap();
+        assert_eq!(vec!["*.in", "*.rs"], config.include);
+        assert_eq!(vec!["*.html", "*.css"], config.exclude[config.exclude.len() - 2..]);
+
+        assert!(matches!(
+            parse(&["minigrep", "-t", "cobol", "x"]),
+            Err(ConfigError::InvalidValue { flag, value }) if flag == "--type" && value == "cobol"
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--type-add", "nocolon", "x"]),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(parse(&["minigrep", "--type-list"]).unwrap().type_list);
+    }
+
+    #[test]
+    fn stdin_by_default() {
+        let config = parse(&["minigrep", "x"]).unwrap();
+        assert_eq!(vec![STDIN_PATH], config.paths);
+    }
+
+    #[test]
+    fn match_spans() {
+        assert_eq!(vec![2..6, 8..12], Pattern::new("body", true).find_spans("a body, body"));
+        assert_eq!(
+            vec![0..4, 7..10],
+            Pattern::Regex(Regex::new(r"\w+o\b").unwrap()).find_spans("Trio - two")
+        );
+        // 'İ'折叠后长度改变，高亮范围仍然要落在原字符串上
+        assert_eq!(vec![4..7], Pattern::new("ist", false).find_spans("İ, Istanbul"));
+    }
+
+    #[test]
+    fn case_and_word_flags() {
+        let config = parse(&["minigrep", "-S", "rust", "f"]).unwrap();
+        assert!(!config.case_sensitive);
+        let config = parse(&["minigrep", "--smart-case", "Rust", "f"]).unwrap();
+        assert!(config.case_sensitive);
+        let config = parse(&["minigrep", "-Si", "Rust", "f"]).unwrap();
+        assert!(!config.case_sensitive);
+
+        let config = parse(&["minigrep", "-wi", "RUST", "f"]).unwrap();
+        assert!(config.whole_word);
+        assert!(config.pattern.is_match("Rust:"));
+        assert!(!config.pattern.is_match("Trust me."));
+        assert_eq!(vec!["Straße"], search_case_insensitive("STRASSE", "Straße\nStrasbourg"));
+    }
+
+    #[test]
+    fn field_flags() {
+        let config = parse(&["minigrep", "--csv", "--field", "city", "oslo", "f"]).unwrap();
+        assert_eq!(Some(Fields::csv(Field::Name(String::from("city")))), config.fields);
+        let config = parse(&["minigrep", "--jsonl", "--path", ".a.0", "x", "f"]).unwrap();
+        assert_eq!(Some(Fields::Json(vec![String::from("a"), String::from("0")])), config.fields);
+        assert!(matches!(
+            parse(&["minigrep", "--tsv", "x", "f"]),
+            Err(ConfigError::MissingFlag("--csv/--tsv", "--field"))
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--jsonl", "--path", "a", "x", "f"]),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--field", "1", "x", "f"]),
+            Err(ConfigError::MissingFlag("--field", _))
+        ));
+    }
+
+    #[test]
+    fn multiline_flag() {
+        let config = parse(&["minigrep", "-U", "-E", r"^fn \w+\($", "f"]).unwrap();
+        assert!(config.multiline);
+        let blocks = search_multiline(&config.pattern, "x\r\nfn main(\r\n) {}\r\n");
+        assert_eq!(vec![2], blocks.iter().flatten().map(|line| line.line_number).collect::<Vec<_>>());
+        assert!(matches!(
+            parse(&["minigrep", "-U", "--replace", "x", "a", "f"]),
+            Err(ConfigError::ConflictingFlags("-U", _))
+        ));
+    }
+
+    #[test]
+    fn multiple_patterns() {
+        let config = parse(&["minigrep", "-e", "safe", "--regexp=Pick", "poem.txt", "f"]).unwrap();
+        assert_eq!(vec!["safe", "Pick"], config.patterns);
+        assert_eq!("safe\nPick", config.query);
+        assert_eq!(vec!["poem.txt", "f"], config.paths);
+        assert!(matches!(config.pattern, Pattern::Literals(_)));
+
+        let config = parse(&["minigrep", "-E", "-e", r"^\d+$", "-e", "(?i)duct"]).unwrap();
+        assert_eq!(vec![STDIN_PATH], config.paths);
+        assert!(config.pattern.is_match("2024") && config.pattern.is_match("DUCT"));
+        assert!(!config.pattern.is_match("x2024"));
+        assert!(matches!(
+            parse(&["minigrep", "-E", "-e", "ok", "-e", "(bad"]),
+            Err(ConfigError::InvalidRegex { pattern, .. }) if pattern == "(bad"
+        ));
+
+        let dir = std::env::temp_dir().join(format!("minigrep-patterns-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("patterns.txt");
+        std::fs::write(&file, "fast\r\nthree\n").unwrap();
+        let file = file.display().to_string();
+        let config = parse(&["minigrep", "-f", &file, "-e", "tape"]).unwrap();
+        assert_eq!(vec!["fast", "three", "tape"], config.patterns);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            parse(&["minigrep", "-f", &file]),
+            Err(ConfigError::PatternFile { .. })
+        ));
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let contents = "\
+Rust:
+safe, fast, prodcutive.
+Pick three.
+Duct tape.";
+        assert_eq!(vec!["safe, fast, prodcutive."], search_fuzzy("productive", 2, contents));
+        assert_eq!(vec!["Pick three."], search_fuzzy("tree", 1, contents));
+
+        let config = parse(&["minigrep", "--fuzzy", "1", "--rank", "-i", "TREE", "f"]).unwrap();
+        assert_eq!((Some(1), true), (config.fuzzy, config.rank));
+        assert!(config.pattern.is_match("Pick three."));
+        assert!(matches!(
+            parse(&["minigrep", "--fuzzy", "3", "abc", "f"]),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--rank", "abc", "f"]),
+            Err(ConfigError::MissingFlag("--rank", "--fuzzy"))
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "--fuzzy=1", "-E", "abc", "f"]),
+            Err(ConfigError::ConflictingFlags("--fuzzy", _))
+        ));
+    }
+
+    #[test]
+    fn errors_do_not_stop_search() {
+        let dir = std::env::temp_dir().join(format!("minigrep-errors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.txt");
+        std::fs::write(&good, "Duct tape.\n").unwrap();
+        std::fs::write(dir.join("latin1.txt"), b"caf\xe9 tape\n").unwrap();
+        let missing = dir.join("missing.txt").display().to_string();
+        let latin1 = dir.join("latin1.txt").display().to_string();
+        let good = good.display().to_string();
+
+        let config = parse(&["minigrep", "-q", "-j", "1", "tape", &missing, &latin1, &good]).unwrap();
+        let outcome = run(config).unwrap();
+        assert_eq!((true, 2), (outcome.matched, outcome.errors));
+        // 和grep一样，-q时只要有匹配就返回0
+        assert_eq!(0, outcome.exit_code());
+        let config = parse(&["minigrep", "-j", "2", "tape", &latin1, &good]).unwrap();
+        assert_eq!(2, run(config).unwrap().exit_code());
+        let config = parse(&["minigrep", "-q", "nothing", &good]).unwrap();
+        assert_eq!(1, run(config).unwrap().exit_code());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn color_flag() {
+        let config = parse(&["minigrep", "--color=always", "x", "f"]).unwrap();
+        assert_eq!(ColorChoice::Always, config.color);
+        let config = parse(&["minigrep", "--color", "x", "f"]).unwrap();
+        assert_eq!(ColorChoice::Auto, config.color);
+        assert_eq!(vec!["f"], config.paths);
+        assert!(parse(&["minigrep", "--color=sometimes", "x", "f"]).is_err());
+    }
+
+    #[test]
+    fn json_flag() {
+        assert!(parse(&["minigrep", "--json", "x", "f"]).unwrap().json);
+        assert!(matches!(
+            parse(&["minigrep", "--json", "-c", "x", "f"]),
+            Err(ConfigError::ConflictingFlags("--json", _))
+        ));
+    }
+
+    #[test]
+    fn replace_flags() {
+        let config = parse(&[
+            "minigrep", "--replace", "$1", "--in-place=.orig", "--dry-run", "-E", "(a)b", "f",
+        ])
+        .unwrap();
+        assert_eq!(Some(String::from("$1")), config.replace);
+        assert!(config.in_place && config.dry_run);
+        assert_eq!(Some(String::from(".orig")), config.backup_suffix);
+
+        assert!(matches!(
+            parse(&["minigrep", "--in-place", "x", "f"]),
+            Err(ConfigError::MissingFlag("--in-place", "--replace"))
+        ));
+    }
+
+    #[test]
+    fn output_modes() {
+        let config = parse(&["minigrep", "-vcm", "5", "x", "f"]).unwrap();
+        assert!(config.invert);
+        assert_eq!(OutputMode::Count, config.output);
+        assert_eq!(Some(5), config.max_count);
+        assert_eq!(0, config.threads);
+        assert!(!config.quiet);
+
+        let config = parse(&["minigrep", "-q", "-L", "-j4", "x", "f"]).unwrap();
+        assert_eq!(4, config.threads);
+        assert_eq!(OutputMode::FilesWithoutMatch, config.output);
+        assert!(config.quiet);
+    }
+}
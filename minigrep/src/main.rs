@@ -3,7 +3,7 @@ use std::env;
 
 use std::process;
 
-use minigrep::Config;
+use minigrep::{Config, ConfigError, MinigrepError, Report, USAGE};
 // 重构前面对的四个问题：
 // 1. 最好将函数拆分开来，让一个函数只负责一项任务。
 // 2. 最好将多个配置变量合并至一个结构体内，从而让它们的用途变得更加清晰。
@@ -34,6 +34,19 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     // println!("{:?}", args);
 
+    // minigrep index build [DIR]...不是一次搜索，不需要经过Config
+    if args.len() >= 3 && args[1] == "index" && args[2] == "build" {
+        match minigrep::build_indexes(&args[3..]) {
+            Ok(0) => {}
+            Ok(_) => process::exit(2),
+            Err(e) => {
+                eprintln!("minigrep: {}", e.report());
+                process::exit(e.exit_code());
+            }
+        }
+        return;
+    }
+
     // main函数已经不需要再关心变量和命令行参数之间的关系了。
     // let config = parse_config(&args);
     // 再次更新：把parse_config改为Config的new函数
@@ -54,10 +67,34 @@ fn main() {
 
     // 闭包的参数被写在两条竖线之间，而unwrap_or_else则会将Err中的值，
     // 也就是示例12-9中添加的not enough arguments，作为参数err传递给闭包❹。闭包中的代码可以在随后运行时使用参数err中的值。
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-       });
+    // --help和--version也是通过Err返回的，它们需要打印到标准输出并正常退出
+    let config = Config::new(&args).unwrap_or_else(|err| match err {
+        ConfigError::HelpRequested => {
+            print!("{}", USAGE);
+            process::exit(0);
+        }
+        ConfigError::VersionRequested => {
+            println!("minigrep {}", env!("CARGO_PKG_VERSION"));
+            process::exit(0);
+        }
+        // 配置文件的问题和命令行无关，不提示--help
+        err @ ConfigError::ConfigFile { .. } => {
+            eprintln!("minigrep: {}", err);
+            process::exit(2);
+        }
+        // 用法错误附带--help的提示，无效的正则表达式、读不了的模式文件则和其他错误一样打印
+        err => match MinigrepError::from(err) {
+            MinigrepError::Usage(err) => {
+                eprintln!("Problem parsing arguments: {}", Report(&err));
+                eprintln!("Try 'minigrep --help' for more information.");
+                process::exit(2);
+            }
+            err => {
+                eprintln!("minigrep: {}", err.report());
+                process::exit(err.exit_code());
+            }
+        },
+    });
 
     // println!("Searching for {}", config.query);
     // println!("In file {}", config.filename);
@@ -67,9 +104,18 @@ fn main() {
     // 并在返回Err值的情况下调用了process::exit(1)。
     // 和Config::new返回一个Config实例不同，run函数并不会返回一个需要进行unwrap的值。
     // 因为run函数在运行成功时返回的是()，而我们只关注产生错误时的情形，所以没有必要调用unwrap_or_else把这个必定是()的值取出来。
-    if let Err(e) = minigrep::run(config){
-        eprintln!("error is {}", e);
-        process::exit(1);
+    // 再次更新：退出码和grep保持一致，0表示有匹配，1表示没有匹配，2表示出错
+    // 单个文件的错误已经在搜索过程中打印过了，这里只根据Outcome决定退出码
+    match minigrep::run(config) {
+        Ok(outcome) => {
+            if outcome.exit_code() != 0 {
+                process::exit(outcome.exit_code());
+            }
+        }
+        Err(e) => {
+            eprintln!("minigrep: {}", e.report());
+            process::exit(e.exit_code());
+        }
     }
 
 
@@ -77,7 +123,6 @@ fn main() {
 
 
 
-
     // 它会打开对应文件并使用Result<String>类型返回文件的内容
     // let contents = fs::read_to_string(filename) 
     //        .expect("Something went wrong reading the file");
@@ -0,0 +1,167 @@
+// -U/--multiline：把整个输入读入内存后再匹配，模式可以跨越换行符，比如-E 'fn \w+\(\n'。
+// 一个匹配覆盖到的每一行都是匹配行，所以跨越多行的匹配会被完整地打印出来，
+// 每一行带着自己的行号，第一行和最后一行的行号就是这个匹配的起止行号。
+// -c统计、-m限制的仍然是行数；-v选中的是没有被任何匹配覆盖的行。
+
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::output::Printer;
+use crate::{selection_limit, split_lines, Config, Match, OutputMode, Pattern};
+
+// 对每一行，返回它被匹配覆盖的部分（相对于这一行的字节范围），没有被覆盖的行是None。
+// 只覆盖了行尾换行符的匹配同样会选中这一行，只是没有需要高亮的部分。
+pub(crate) fn covered_lines(contents: &str, lines: &[Match], spans: &[Range<usize>]) -> Vec<Option<Vec<Range<usize>>>> {
+    // 每一行连同换行符一起的结束位置
+    let ends: Vec<usize> = lines
+        .iter()
+        .skip(1)
+        .map(|line| line.byte_range.start)
+        .chain(std::iter::once(contents.len()))
+        .collect();
+    let mut covered = vec![None; lines.len()];
+    for span in spans {
+        let first = ends.partition_point(|end| *end <= span.start);
+        for (index, line) in lines.iter().enumerate().skip(first) {
+            if line.byte_range.start >= span.end {
+                break;
+            }
+            let start = span.start.max(line.byte_range.start);
+            let end = span.end.min(line.byte_range.end);
+            let parts = covered[index].get_or_insert_with(Vec::new);
+            if start < end {
+                parts.push(start - line.byte_range.start..end - line.byte_range.start);
+            }
+        }
+    }
+    covered
+}
+
+// 返回所有匹配块，相邻或重叠的匹配所覆盖的行合并为一块
+pub fn search_multiline<'a>(pattern: &Pattern, contents: &'a str) -> Vec<Vec<Match<'a>>> {
+    let lines = split_lines(contents);
+    let covered = covered_lines(contents, &lines, &pattern.find_spans(contents));
+    let mut blocks: Vec<Vec<Match>> = Vec::new();
+    let mut previous = None;
+    for (index, (line, spans)) in lines.into_iter().zip(covered).enumerate() {
+        if spans.is_none() {
+            continue;
+        }
+        match blocks.last_mut() {
+            Some(block) if previous == Some(index - 1) => block.push(line),
+            _ => blocks.push(vec![line]),
+        }
+        previous = Some(index);
+    }
+    blocks
+}
+
+// search_input的-U版本，返回被选中的行数
+pub(crate) fn search_input<R: BufRead, W: Write>(
+    config: &Config,
+    printer: &mut Printer,
+    out: &mut W,
+    path: &Path,
+    mut reader: R,
+) -> io::Result<usize> {
+    printer.begin_file();
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    let lines = split_lines(&contents);
+    let covered = covered_lines(&contents, &lines, &config.pattern.find_spans(&contents));
+
+    let print_lines = config.output == OutputMode::Lines && !config.quiet;
+    let highlight = (printer.colors.is_some() || printer.json) && !config.invert;
+    let limit = selection_limit(config);
+    let mut count = 0;
+    for (line, spans) in lines.iter().zip(covered) {
+        if limit.is_some_and(|max| count >= max) {
+            if print_lines && printer.in_after_context() {
+                printer.line(out, path, line, false, &[])?;
+                continue;
+            }
+            break;
+        }
+        let selected = spans.is_some() != config.invert;
+        if selected {
+            count += 1;
+        }
+        if print_lines {
+            let spans = match spans {
+                Some(spans) if highlight => spans,
+                _ => Vec::new(),
+            };
+            printer.line(out, path, line, selected, &spans)?;
+        }
+    }
+    if print_lines {
+        printer.end_file(out, path, count)?;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::RegexBuilder;
+
+    fn regex(pattern: &str) -> Pattern {
+        Pattern::Regex(RegexBuilder::new(pattern).multi_line(true).crlf(true).build().unwrap())
+    }
+
+    #[test]
+    fn matches_span_lines() {
+        let contents = "\
+fn parse(
+    input: &str,
+) -> Config {
+}
+fn main() {
+fn run(
+    config: Config,
+) {";
+        let blocks = search_multiline(&regex(r"fn \w+\(\n(?:.*\n)*?\)"), contents);
+        let numbers: Vec<Vec<usize>> = blocks
+            .iter()
+            .map(|block| block.iter().map(|line| line.line_number).collect())
+            .collect();
+        assert_eq!(vec![vec![1, 2, 3], vec![6, 7, 8]], numbers);
+        assert_eq!("fn parse(", blocks[0][0].line);
+
+        // 字面量模式中同样可以包含换行符
+        let blocks = search_multiline(&Pattern::new("b\nc", true), "a\nab\nc\nd");
+        assert_eq!(1, blocks.len());
+        assert_eq!(vec!["ab", "c"], blocks[0].iter().map(|line| line.line).collect::<Vec<_>>());
+        // 开启crlf之后，$可以匹配\r\n中\r之前的位置
+        let blocks = search_multiline(&regex(r"b$\r?\nc"), "a\r\nab\r\nc\r\nd");
+        assert_eq!(vec!["ab", "c"], blocks[0].iter().map(|line| line.line).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn case_insensitive_across_a_large_input() {
+        // 折叠后长度会变的字符（ß、İ）分布在整个内容中，每个匹配的位置都要准确地映射回去
+        let contents: String = (0..5000).map(|i| format!("{} İx Straße\nERROR ß {}\n--\n", i, i)).collect();
+        let pattern = Pattern::new("STRASSE\nerror ss", false);
+        let spans = pattern.find_spans(&contents);
+        assert_eq!(5000, spans.len());
+        assert!(spans.iter().all(|span| &contents[span.clone()] == "Straße\nERROR ß"));
+
+        let blocks = search_multiline(&pattern, &contents);
+        assert_eq!(5000, blocks.len());
+        let last = &blocks[4999];
+        assert_eq!((14998, 14999), (last[0].line_number, last[1].line_number));
+        assert_eq!("4999 İx Straße", last[0].line);
+    }
+
+    #[test]
+    fn covered_parts() {
+        let contents = "one\ntwo\nthree\n";
+        let lines = split_lines(contents);
+        // "e\ntw"跨越前两行，"\n"只覆盖第二行的换行符
+        let covered = covered_lines(contents, &lines, &[0..1, 2..6, 7..8]);
+        assert_eq!(Some(&[0..1, 2..3][..]), covered[0].as_deref());
+        assert_eq!(Some(0..2), covered[1].as_ref().and_then(|parts| parts.first().cloned()));
+        assert_eq!(None, covered[2]);
+    }
+}
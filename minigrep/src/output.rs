@@ -0,0 +1,444 @@
+// 负责把匹配结果按照grep的格式打印出来：
+// • 匹配行的各个前缀之间用':'分隔，上下文行用'-'分隔
+// • 不相邻的两组输出之间打印一行"--"
+// • 开启颜色时，用ANSI转义序列给文件名、行号、分隔符和匹配到的文本上色
+// • --json时每条记录输出为一行JSON（JSON Lines），格式如下：
+//   {"type":"begin","data":{"path":"poem.txt"}}
+//   {"type":"match","data":{"path":"poem.txt","line_number":1,"absolute_offset":0,
+//                           "line":"I'm nobody!","submatches":[{"match":"body","start":6,"end":10}]}}
+//   {"type":"context","data":{...和match相同，submatches为空...}}
+//   {"type":"end","data":{"path":"poem.txt","matched_lines":1}}
+//   {"type":"summary","data":{"searches":2,"searches_with_match":1,"matched_lines":1}}
+//   submatches中的start/end是相对于该行的字节偏移，加上absolute_offset就是在文件中的偏移。
+//   --fuzzy时match记录还有一个"distance"字段，文本输出则在行首加上"~距离@起点-终点"。
+
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::fuzzy::FuzzyMatch;
+use crate::searcher::Context;
+use crate::{ContextKind, Match, Sink};
+
+// 各部分使用的SGR参数，默认值和GNU grep一致
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colors {
+    pub path: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+    pub matched: String,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            path: String::from("35"),
+            line_number: String::from("32"),
+            byte_offset: String::from("32"),
+            separator: String::from("36"),
+            matched: String::from("01;31"),
+        }
+    }
+}
+
+// Printer是逐行工作的：调用者把每一行连同“是否匹配”交给它，它自己决定要不要打印。
+// 这样无论内容来自内存还是来自标准输入这样的流，都可以使用同一套上下文逻辑。
+// 上下文的取舍由searcher::Context决定，和Searcher是同一份实现；Printer只负责格式。
+// 已经在用Searcher搜索的调用者则直接把PrinterSink交给Searcher。
+#[derive(Clone)]
+pub struct Printer {
+    pub with_path: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    // None表示不上色
+    pub colors: Option<Colors>,
+    pub json: bool,
+    // 是否已经输出过内容，用来决定下一个文件的输出前要不要打印"--"
+    printed_group: bool,
+    // 以下状态只在当前文件内有效，begin_file会重置它们
+    file_printed: bool,
+    context: Context,
+    // JSON模式下当前文件是否已经输出过begin记录
+    began: bool,
+}
+
+impl Printer {
+    pub fn new(
+        with_path: bool,
+        line_number: bool,
+        byte_offset: bool,
+        before_context: usize,
+        after_context: usize,
+    ) -> Printer {
+        Printer {
+            with_path,
+            line_number,
+            byte_offset,
+            before_context,
+            after_context,
+            colors: None,
+            json: false,
+            printed_group: false,
+            file_printed: false,
+            context: Context::new(before_context, after_context),
+            began: false,
+        }
+    }
+
+    pub fn begin_file(&mut self) {
+        self.file_printed = false;
+        self.context = Context::new(self.before_context, self.after_context);
+        self.began = false;
+    }
+
+    // JSON模式下，为输出过内容的文件补上end记录
+    pub fn end_file<W: Write>(&mut self, out: &mut W, path: &Path, count: usize) -> io::Result<()> {
+        if self.json && self.began {
+            let data = json!({ "path": path.to_string_lossy(), "matched_lines": count });
+            write_record(out, "end", data)?;
+        }
+        Ok(())
+    }
+
+    // JSON模式下，在全部搜索结束后输出汇总记录
+    pub fn summary<W: Write>(
+        &self,
+        out: &mut W,
+        searches: usize,
+        searches_with_match: usize,
+        matched_lines: usize,
+    ) -> io::Result<()> {
+        if self.json {
+            let data = json!({
+                "searches": searches,
+                "searches_with_match": searches_with_match,
+                "matched_lines": matched_lines,
+            });
+            write_record(out, "summary", data)?;
+        }
+        Ok(())
+    }
+
+    // 是否还有没打印完的后置上下文
+    pub fn in_after_context(&self) -> bool {
+        self.context.in_after_context()
+    }
+
+    // spans是匹配行中需要高亮的字节范围，不上色时可以传空
+    pub fn line<W: Write>(
+        &mut self,
+        out: &mut W,
+        path: &Path,
+        line: &Match,
+        is_match: bool,
+        spans: &[Range<usize>],
+    ) -> io::Result<()> {
+        let mut context = mem::take(&mut self.context);
+        let mut sink = PrinterSink {
+            printer: self,
+            out,
+            path,
+        };
+        let result = if is_match {
+            context.selected(&mut sink, line, spans)
+        } else {
+            context.other(&mut sink, line)
+        };
+        self.context = context;
+        result.map(|_| ())
+    }
+
+    // -l/-L使用：只打印文件名
+    pub fn path<W: Write>(&self, out: &mut W, path: &Path) -> io::Result<()> {
+        paint(out, self.colors.as_ref().map(|c| &c.path), path.display())?;
+        writeln!(out)
+    }
+
+    // -c使用：打印每个文件选中的行数
+    pub fn count<W: Write>(&self, out: &mut W, path: &Path, count: usize) -> io::Result<()> {
+        if self.with_path {
+            paint(out, self.colors.as_ref().map(|c| &c.path), path.display())?;
+            paint(out, self.colors.as_ref().map(|c| &c.separator), ':')?;
+        }
+        writeln!(out, "{}", count)
+    }
+
+    // 有上下文时不相邻的两组输出之间的"--"。并行搜索时由主线程在两个文件的输出之间调用
+    pub fn file_separator<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if !self.json && (self.before_context > 0 || self.after_context > 0) {
+            paint(out, self.colors.as_ref().map(|c| &c.separator), "--")?;
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    // 文件内不相邻的两组输出之间的"--"由Context通过context_break决定，
+    // 这里只处理文件之间的：一个文件第一次打印时，之前的文件如果打印过内容，也要先打印"--"
+    fn separate<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        if !self.file_printed {
+            if self.printed_group {
+                self.file_separator(out)?;
+            }
+            self.file_printed = true;
+            self.printed_group = true;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn print<W: Write>(
+        &mut self,
+        out: &mut W,
+        path: &Path,
+        number: usize,
+        offset: usize,
+        text: &str,
+        sep: char,
+        spans: &[Range<usize>],
+        fuzzy: Option<&FuzzyMatch>,
+    ) -> io::Result<()> {
+        self.separate(out)?;
+        if self.json {
+            return self.print_json(out, path, number, offset, text, sep, spans, fuzzy);
+        }
+
+        let colors = self.colors.as_ref();
+        let sep_color = colors.map(|c| &c.separator);
+        if self.with_path {
+            paint(out, colors.map(|c| &c.path), path.display())?;
+            paint(out, sep_color, sep)?;
+        }
+        if self.line_number {
+            paint(out, colors.map(|c| &c.line_number), number)?;
+            paint(out, sep_color, sep)?;
+        }
+        if self.byte_offset {
+            paint(out, colors.map(|c| &c.byte_offset), offset)?;
+            paint(out, sep_color, sep)?;
+        }
+        if let Some(m) = fuzzy {
+            let field = format!("~{}@{}-{}", m.distance, m.span.start, m.span.end);
+            paint(out, colors.map(|c| &c.byte_offset), field)?;
+            paint(out, sep_color, sep)?;
+        }
+
+        let matched = colors.map(|c| &c.matched);
+        let mut pos = 0;
+        for span in spans {
+            if matched.is_none() || span.start < pos {
+                continue;
+            }
+            write!(out, "{}", &text[pos..span.start])?;
+            paint(out, matched, &text[span.clone()])?;
+            pos = span.end;
+        }
+        writeln!(out, "{}", &text[pos..])
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn print_json<W: Write>(
+        &mut self,
+        out: &mut W,
+        path: &Path,
+        number: usize,
+        offset: usize,
+        text: &str,
+        sep: char,
+        spans: &[Range<usize>],
+        fuzzy: Option<&FuzzyMatch>,
+    ) -> io::Result<()> {
+        let path = path.to_string_lossy();
+        if !self.began {
+            write_record(out, "begin", json!({ "path": path }))?;
+            self.began = true;
+        }
+        let submatches: Vec<Value> = spans
+            .iter()
+            .map(|span| json!({ "match": &text[span.clone()], "start": span.start, "end": span.end }))
+            .collect();
+        let mut data = json!({
+            "path": path,
+            "line_number": number,
+            "absolute_offset": offset,
+            "line": text,
+            "submatches": submatches,
+        });
+        if let Some(m) = fuzzy {
+            data["distance"] = json!(m.distance);
+        }
+        write_record(out, if sep == ':' { "match" } else { "context" }, data)
+    }
+}
+
+// 把Searcher或者Context送来的行交给Printer打印
+pub struct PrinterSink<'a, W> {
+    pub printer: &'a mut Printer,
+    pub out: &'a mut W,
+    pub path: &'a Path,
+}
+
+impl<W: Write> PrinterSink<'_, W> {
+    // --fuzzy使用：打印一个近似匹配行，行首带有编辑距离和匹配范围
+    pub fn fuzzy_matched(&mut self, line: &Match, spans: &[Range<usize>], fuzzy: &FuzzyMatch) -> io::Result<bool> {
+        let (number, offset) = (line.line_number, line.byte_range.start);
+        self.printer
+            .print(self.out, self.path, number, offset, line.line, ':', spans, Some(fuzzy))?;
+        Ok(true)
+    }
+}
+
+impl<W: Write> Sink for PrinterSink<'_, W> {
+    fn matched(&mut self, line: &Match, spans: &[Range<usize>]) -> io::Result<bool> {
+        let (number, offset) = (line.line_number, line.byte_range.start);
+        self.printer
+            .print(self.out, self.path, number, offset, line.line, ':', spans, None)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, line: &Match, _kind: ContextKind) -> io::Result<bool> {
+        let (number, offset) = (line.line_number, line.byte_range.start);
+        self.printer
+            .print(self.out, self.path, number, offset, line.line, '-', &[], None)?;
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.printer.file_separator(self.out)
+    }
+
+    // 只有上色和JSON输出才需要匹配的位置
+    fn needs_spans(&self) -> bool {
+        self.printer.colors.is_some() || self.printer.json
+    }
+}
+
+fn write_record<W: Write>(out: &mut W, kind: &str, data: Value) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, &json!({ "type": kind, "data": data }))?;
+    writeln!(out)
+}
+
+fn paint<W: Write, T: Display>(out: &mut W, color: Option<&String>, value: T) -> io::Result<()> {
+    match color {
+        Some(color) => write!(out, "\x1b[{}m{}\x1b[0m", color, value),
+        None => write!(out, "{}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_lines;
+
+    fn render(printer: &mut Printer, contents: &str, matched: &[usize]) -> String {
+        let mut out = Vec::new();
+        printer.begin_file();
+        for (index, line) in split_lines(contents).iter().enumerate() {
+            printer
+                .line(&mut out, Path::new("poem.txt"), line, matched.contains(&index), &[])
+                .unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut printer = Printer::new(true, true, true, 0, 0);
+        assert_eq!("poem.txt:2:2:b\n", render(&mut printer, "a\nb\nc", &[1]));
+    }
+
+    #[test]
+    fn context_groups() {
+        let contents = "1\n2\n3\n4\n5\n6\n7\n8\n9";
+        let mut printer = Printer::new(false, true, false, 1, 1);
+        assert_eq!(
+            "1:1\n2-2\n--\n4-4\n5:5\n6:6\n7-7\n",
+            render(&mut printer, contents, &[0, 4, 5])
+        );
+    }
+
+    #[test]
+    fn separator_between_files() {
+        let mut printer = Printer::new(false, false, false, 0, 1);
+        assert_eq!("a\nb\n", render(&mut printer, "a\nb\nc", &[0]));
+        assert_eq!("--\nc\n", render(&mut printer, "a\nb\nc", &[2]));
+    }
+
+    #[test]
+    fn colored_output() {
+        let mut printer = Printer::new(true, true, false, 0, 0);
+        printer.colors = Some(Colors::default());
+        let line = &split_lines("a body here")[0];
+        let span = 2..6;
+        let mut out = Vec::new();
+        printer
+            .line(&mut out, Path::new("poem.txt"), line, true, std::slice::from_ref(&span))
+            .unwrap();
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m\x1b[36m:\x1b[0m\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m\
+             a \x1b[01;31mbody\x1b[0m here\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_records() {
+        let mut printer = Printer::new(false, false, false, 0, 1);
+        printer.json = true;
+        printer.begin_file();
+        let span = 2..6;
+        let mut out = Vec::new();
+        let path = Path::new("poem.txt");
+        let lines = split_lines("a body\nnext\nlast");
+        printer.line(&mut out, path, &lines[0], true, std::slice::from_ref(&span)).unwrap();
+        printer.line(&mut out, path, &lines[1], false, &[]).unwrap();
+        printer.line(&mut out, path, &lines[2], false, &[]).unwrap();
+        printer.end_file(&mut out, path, 1).unwrap();
+
+        let records: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(4, records.len());
+        assert_eq!(json!({"type": "begin", "data": {"path": "poem.txt"}}), records[0]);
+        assert_eq!(
+            json!({"type": "match", "data": {
+                "path": "poem.txt", "line_number": 1, "absolute_offset": 0, "line": "a body",
+                "submatches": [{"match": "body", "start": 2, "end": 6}],
+            }}),
+            records[1]
+        );
+        assert_eq!("context", records[2]["type"]);
+        assert_eq!(7, records[2]["data"]["absolute_offset"]);
+        assert_eq!(json!({"type": "end", "data": {"path": "poem.txt", "matched_lines": 1}}), records[3]);
+    }
+
+    #[test]
+    fn fuzzy_prefix() {
+        let mut printer = Printer::new(false, true, false, 0, 0);
+        let line = &split_lines("a bdy")[0];
+        let fuzzy = FuzzyMatch { distance: 1, span: 2..5 };
+        let mut out = Vec::new();
+        let path = Path::new("poem.txt");
+        PrinterSink { printer: &mut printer, out: &mut out, path }
+            .fuzzy_matched(line, &[], &fuzzy)
+            .unwrap();
+        assert_eq!("1:~1@2-5:a bdy\n", String::from_utf8(out).unwrap());
+
+        printer.json = true;
+        let mut out = Vec::new();
+        let path = Path::new("poem.txt");
+        PrinterSink { printer: &mut printer, out: &mut out, path }
+            .fuzzy_matched(line, &[], &fuzzy)
+            .unwrap();
+        let record: Value = serde_json::from_str(String::from_utf8(out).unwrap().lines().nth(1).unwrap()).unwrap();
+        assert_eq!(1, record["data"]["distance"]);
+    }
+}
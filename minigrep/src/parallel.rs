@@ -0,0 +1,117 @@
+// 多线程搜索多个文件。
+// 和bingfa/中的例子一样：任务通过mpsc通道分发，接收端放在Arc<Mutex<_>>中由所有工作线程共享。
+// 每个文件有自己的一条有界通道，输出被切成小块发回主线程，主线程按照文件原本的顺序依次读取：
+// • 正在读取的文件的输出直接写到标准输出，不会整个留在内存中
+// • 排在后面的文件最多缓冲CHUNK_BUFFER块，通道满了之后它的工作线程会等待
+// 所以无论哪个线程先完成，最终的输出都和单线程搜索时完全相同，内存占用也不会随文件大小增长。
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::output::Printer;
+use crate::{input_label, search_path, Config, MinigrepError};
+
+// 每块输出的大小，以及每个文件最多缓冲的块数
+const CHUNK_SIZE: usize = 64 * 1024;
+const CHUNK_BUFFER: usize = 4;
+
+// 按照文件的顺序产出的事件：一个文件的若干块输出，之后是它的搜索结果
+pub enum Event {
+    Output(Vec<u8>),
+    // 和search_path的返回值相同：是否被选中以及选中的行数，二进制文件为None
+    Done(Result<Option<(bool, usize)>, MinigrepError>),
+}
+
+type Job = (PathBuf, SyncSender<Event>);
+
+pub struct Ordered {
+    // 还没有读完的文件，第一个就是正在读取的文件
+    files: VecDeque<Receiver<Event>>,
+}
+
+pub fn search_files(config: Arc<Config>, printer: &Printer, files: Vec<PathBuf>, threads: usize) -> Ordered {
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    for _ in 0..threads.min(files.len()) {
+        let job_rx = Arc::clone(&job_rx);
+        let config = Arc::clone(&config);
+        let printer = printer.clone();
+
+        thread::spawn(move || loop {
+            // 锁只在取任务时持有，离开这条语句后就会被释放，其他线程可以继续取任务
+            let job = job_rx.lock().unwrap().recv();
+            let (path, events) = match job {
+                Ok(job) => job,
+                // 发送端已经关闭，所有任务都分发完了
+                Err(_) => break,
+            };
+
+            let mut printer = printer.clone();
+            let mut output = Chunks {
+                chunk: Vec::new(),
+                events,
+            };
+            let result = search_path(&config, &mut printer, &mut output, &path)
+                .map_err(|err| MinigrepError::from_io(input_label(&path), err));
+            // 主线程提前结束（比如-q已经找到匹配）时接收端会被丢弃，这时直接退出即可
+            if output.flush().is_err() || output.events.send(Event::Done(result)).is_err() {
+                break;
+            }
+        });
+    }
+
+    let mut ordered = Ordered {
+        files: VecDeque::with_capacity(files.len()),
+    };
+    for path in files {
+        let (events_tx, events_rx) = mpsc::sync_channel(CHUNK_BUFFER);
+        job_tx.send((path, events_tx)).unwrap();
+        ordered.files.push_back(events_rx);
+    }
+    ordered
+}
+
+impl Iterator for Ordered {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.files.front()?.recv().ok()?;
+        if let Event::Done(_) = event {
+            self.files.pop_front();
+        }
+        Some(event)
+    }
+}
+
+// 工作线程的输出：攒够CHUNK_SIZE字节就发给主线程
+struct Chunks {
+    chunk: Vec<u8>,
+    events: SyncSender<Event>,
+}
+
+impl Write for Chunks {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let chunk = mem::take(&mut self.chunk);
+        // 和写一个已经关闭的管道一样，搜索会停下来
+        self.events
+            .send(Event::Output(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
@@ -0,0 +1,370 @@
+// 判断一行是否匹配，以及找出匹配的具体位置。
+// 不区分大小写的搜索使用Unicode大小写折叠（case folding）而不是简单的to_lowercase：
+// • ß、ẞ折叠为ss，所以"STRASSE"能匹配"Straße"
+// • 词尾的ς和Σ、σ折叠为同一个字符
+// • 土耳其语的İ折叠为i（去掉上面的点），ﬁ这样的连字折叠为fi
+// 折叠后的字符串长度可能和原来不同，所有返回的范围都会被映射回原字符串的字节位置。
+// 查找一行中的所有匹配时，这一行只折叠一次。
+// 多个普通文本模式（-e/-f）会被编译成一个Aho-Corasick自动机，
+// 无论有多少个模式，每一行都只需要扫描一遍。
+
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, BuildError, Input, MatchKind};
+use regex::{Regex, RegexBuilder};
+
+use crate::fuzzy::{Fuzzy, FuzzyMatch};
+
+pub enum Pattern {
+    Literal(String),
+    // 保存的是已经折叠过大小写的query
+    CaseInsensitive(String),
+    Regex(Regex),
+    // 多个普通文本模式，任意一个匹配即可
+    Literals(AhoCorasick),
+    // 同上，自动机由折叠过大小写的模式构建
+    CaseInsensitiveLiterals(AhoCorasick),
+    // --fuzzy：编辑距离不超过K的近似匹配
+    Fuzzy(Fuzzy),
+    // -w：只接受前后都不是单词字符的匹配
+    Word(Box<Pattern>),
+}
+
+impl Pattern {
+    pub fn new(query: &str, case_sensitive: bool) -> Pattern {
+        if case_sensitive {
+            Pattern::Literal(query.to_string())
+        } else {
+            Pattern::CaseInsensitive(fold_case(query))
+        }
+    }
+
+    // 正则表达式模式，和-E相同
+    pub fn regex(pattern: &str, case_sensitive: bool) -> Result<Pattern, regex::Error> {
+        let re = RegexBuilder::new(pattern).case_insensitive(!case_sensitive).build()?;
+        Ok(Pattern::Regex(re))
+    }
+
+    // 多个模式时构建Aho-Corasick自动机，只有一个模式时和Pattern::new相同
+    pub fn literals(patterns: &[String], case_sensitive: bool) -> Result<Pattern, BuildError> {
+        if let [query] = patterns {
+            return Ok(Pattern::new(query, case_sensitive));
+        }
+        // 同一位置有多个模式匹配时选择最长的那个，高亮和替换的范围才完整
+        let builder = AhoCorasick::builder().match_kind(MatchKind::LeftmostLongest).clone();
+        if case_sensitive {
+            Ok(Pattern::Literals(builder.build(patterns)?))
+        } else {
+            let folded: Vec<String> = patterns.iter().map(|p| fold_case(p)).collect();
+            Ok(Pattern::CaseInsensitiveLiterals(builder.build(folded)?))
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Pattern::Literal(query) => line.contains(query.as_str()),
+            Pattern::CaseInsensitive(query) => fold_case(line).contains(query.as_str()),
+            Pattern::Regex(re) => re.is_match(line),
+            Pattern::Literals(ac) => ac.is_match(line),
+            Pattern::CaseInsensitiveLiterals(ac) => ac.is_match(&fold_case(line)),
+            Pattern::Fuzzy(fuzzy) => fuzzy.find(line).is_some(),
+            Pattern::Word(_) => self.find_at(line, 0).is_some(),
+        }
+    }
+
+    // 返回line中所有匹配的字节范围（不重叠、按顺序），用于高亮显示
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let folded = self.folds_case().then(|| Folded::new(line));
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        while let Some(span) = self.find_in(line, folded.as_ref(), pos) {
+            if span.is_empty() {
+                // 空匹配不需要高亮，但要向前移动一个字符以免死循环
+                match next_char_boundary(line, span.end) {
+                    Some(next) => pos = next,
+                    None => break,
+                }
+            } else {
+                pos = span.end;
+                spans.push(span);
+            }
+        }
+        spans
+    }
+
+    // 近似匹配模式下，返回这一行中最接近的匹配；其他模式返回None
+    pub fn fuzzy_match(&self, line: &str) -> Option<FuzzyMatch> {
+        match self {
+            Pattern::Fuzzy(fuzzy) => fuzzy.find(line),
+            Pattern::Word(inner) => {
+                let Pattern::Fuzzy(fuzzy) = &**inner else {
+                    return None;
+                };
+                // 和find_at中的Word分支相同，跳过不在单词边界上的匹配
+                let mut pos = 0;
+                loop {
+                    let m = fuzzy.find(&line[pos..])?;
+                    let span = pos + m.span.start..pos + m.span.end;
+                    if is_word_boundary(line, span.start) && is_word_boundary(line, span.end) {
+                        return Some(FuzzyMatch { distance: m.distance, span });
+                    }
+                    pos = next_char_boundary(line, span.start)?;
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // 是否在折叠过大小写的内容中查找
+    fn folds_case(&self) -> bool {
+        match self {
+            Pattern::CaseInsensitive(_) | Pattern::CaseInsensitiveLiterals(_) => true,
+            Pattern::Word(inner) => inner.folds_case(),
+            _ => false,
+        }
+    }
+
+    // 从pos开始查找第一个匹配
+    pub(crate) fn find_at(&self, line: &str, pos: usize) -> Option<Range<usize>> {
+        let folded = self.folds_case().then(|| Folded::new(line));
+        self.find_in(line, folded.as_ref(), pos)
+    }
+
+    // folded是折叠过大小写的line，只有folds_case()为真时才需要
+    fn find_in(&self, line: &str, folded: Option<&Folded>, pos: usize) -> Option<Range<usize>> {
+        match self {
+            Pattern::Literal(query) => line[pos..]
+                .find(query.as_str())
+                .map(|start| pos + start..pos + start + query.len()),
+            Pattern::CaseInsensitive(query) => {
+                let folded = folded?;
+                let from = folded.fold_pos(pos);
+                let start = from + folded.text[from..].find(query.as_str())?;
+                Some(folded.unfold(start..start + query.len()))
+            }
+            Pattern::Regex(re) => re.find_at(line, pos).map(|m| m.range()),
+            Pattern::Literals(ac) => ac.find(Input::new(line).span(pos..line.len())).map(|m| m.range()),
+            Pattern::CaseInsensitiveLiterals(ac) => {
+                let folded = folded?;
+                let input = Input::new(&folded.text).span(folded.fold_pos(pos)..folded.text.len());
+                ac.find(input).map(|m| folded.unfold(m.range()))
+            }
+            // 近似匹配找的是剩余部分中距离最小的那个
+            Pattern::Fuzzy(fuzzy) => fuzzy.find(&line[pos..]).map(|m| pos + m.span.start..pos + m.span.end),
+            Pattern::Word(inner) => {
+                let mut pos = pos;
+                loop {
+                    let span = inner.find_in(line, folded, pos)?;
+                    if is_word_boundary(line, span.start) && is_word_boundary(line, span.end) {
+                        return Some(span);
+                    }
+                    // 这个位置不是完整的单词，从下一个字符开始重新查找
+                    pos = next_char_boundary(line, span.start)?;
+                }
+            }
+        }
+    }
+}
+
+// 和grep -w一样，单词字符是Unicode字母、数字以及下划线
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// at前后的两个字符中最多只有一个是单词字符时，at就是一个单词边界
+fn is_word_boundary(line: &str, at: usize) -> bool {
+    let before = line[..at].chars().next_back().is_some_and(is_word_char);
+    let after = line[at..].chars().next().is_some_and(is_word_char);
+    !(before && after)
+}
+
+fn next_char_boundary(line: &str, at: usize) -> Option<usize> {
+    line[at..].chars().next().map(|c| at + c.len_utf8())
+}
+
+// 把一个字符的大小写折叠结果追加到folded
+fn push_folded(c: char, folded: &mut String) {
+    match c {
+        'ß' | 'ẞ' => folded.push_str("ss"),
+        'ς' => folded.push('σ'),
+        'İ' => folded.push('i'),
+        'ſ' => folded.push('s'),
+        'ﬀ' => folded.push_str("ff"),
+        'ﬁ' => folded.push_str("fi"),
+        'ﬂ' => folded.push_str("fl"),
+        'ﬃ' => folded.push_str("ffi"),
+        'ﬄ' => folded.push_str("ffl"),
+        'ﬅ' | 'ﬆ' => folded.push_str("st"),
+        'µ' => folded.push('μ'),
+        'ϐ' => folded.push('β'),
+        'ϑ' => folded.push('θ'),
+        'ϕ' => folded.push('φ'),
+        'ϖ' => folded.push('π'),
+        'ϰ' => folded.push('κ'),
+        'ϱ' => folded.push('ρ'),
+        'ϵ' => folded.push('ε'),
+        _ => folded.extend(c.to_lowercase()),
+    }
+}
+
+pub fn fold_case(s: &str) -> String {
+    let mut folded = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_folded(c, &mut folded);
+    }
+    folded
+}
+
+// 折叠过大小写的一行，以及它和原字符串之间的位置映射
+pub(crate) struct Folded<'a> {
+    line: &'a str,
+    pub text: String,
+    // 每一段在折叠结果和原字符串中的起始位置，都在字符边界上。
+    // 一段要么是连续的折叠前后字节数相同的字符，逐字节对应；要么是一个字节数改变了的字符（比如ß、İ）。
+    // 大部分文本只有一段，映射表不随内容的长度增长
+    segments: Vec<(usize, usize)>,
+}
+
+impl<'a> Folded<'a> {
+    pub fn new(line: &'a str) -> Folded<'a> {
+        let mut text = String::with_capacity(line.len());
+        let mut segments = vec![(0, 0)];
+        // 最后一段是否可以继续延长
+        let mut extendable = true;
+        for (index, c) in line.char_indices() {
+            let before = text.len();
+            push_folded(c, &mut text);
+            let same = text.len() - before == c.len_utf8();
+            if index > 0 && !(same && extendable) {
+                segments.push((before, index));
+            }
+            extendable = same;
+        }
+        Folded { line, text, segments }
+    }
+
+    // 第i段在两个字符串中的范围
+    fn segment(&self, i: usize) -> (Range<usize>, Range<usize>) {
+        let (folded, orig) = self.segments[i];
+        let (next_folded, next_orig) = self.segments.get(i + 1).copied().unwrap_or((self.text.len(), self.line.len()));
+        (folded..next_folded, orig..next_orig)
+    }
+
+    // 原字符串中字符边界pos在折叠结果中的位置
+    pub fn fold_pos(&self, pos: usize) -> usize {
+        let i = self.segments.partition_point(|&(_, orig)| orig <= pos) - 1;
+        let (folded, orig) = self.segment(i);
+        if folded.len() == orig.len() {
+            folded.start + (pos - orig.start)
+        } else if pos == orig.start {
+            folded.start
+        } else {
+            folded.end
+        }
+    }
+
+    // 折叠结果中第at个字节来自原字符串中的哪个字符
+    fn origin(&self, at: usize) -> Range<usize> {
+        let i = self.segments.partition_point(|&(folded, _)| folded <= at) - 1;
+        let (folded, orig) = self.segment(i);
+        if folded.len() != orig.len() {
+            return orig;
+        }
+        let mut start = orig.start + (at - folded.start);
+        while !self.line.is_char_boundary(start) {
+            start -= 1;
+        }
+        let len = self.line[start..].chars().next().map_or(0, char::len_utf8);
+        start..start + len
+    }
+
+    // 把折叠结果中的范围映射回原字符串，只覆盖字符的一部分时扩展到整个字符
+    pub fn unfold(&self, range: Range<usize>) -> Range<usize> {
+        if range.start >= self.text.len() {
+            return self.line.len()..self.line.len();
+        }
+        let start = self.origin(range.start).start;
+        if range.is_empty() {
+            return start..start;
+        }
+        start..self.origin(range.end - 1).end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_case_folding() {
+        assert!(Pattern::new("STRASSE", false).is_match("Straße"));
+        assert!(Pattern::new("straße", false).is_match("STRASSE"));
+        assert!(Pattern::new("ΟΔΟΣ", false).is_match("οδος"));
+        assert!(Pattern::new("οδοσ", false).is_match("ΟΔΟΣ και οδός"));
+        assert!(Pattern::new("istanbul", false).is_match("İstanbul"));
+        assert!(Pattern::new("file", false).is_match("ﬁle"));
+    }
+
+    #[test]
+    fn spans_map_back_to_original_bytes() {
+        // "ß"占2个字节，折叠后是"ss"，范围必须覆盖整个ß
+        assert_eq!(vec![3..10], Pattern::new("strasse", false).find_spans("Da Straße!"));
+        assert_eq!(vec![0..4], Pattern::new("ist", false).find_spans("İstanbul"));
+        // 只匹配ß折叠结果的一半时，范围扩展到整个字符
+        assert_eq!(vec![2..4], Pattern::new("s", false).find_spans("Fuß"));
+    }
+
+    #[test]
+    fn folded_positions() {
+        // İ折叠后变短，Ⱥ折叠后变长，ß的字节数不变但不是逐字节对应
+        let line = "aİbȺcßdé";
+        let folded = Folded::new(line);
+        assert_eq!("aibⱥcssdé", folded.text);
+        for (index, _) in line.char_indices() {
+            let at = folded.fold_pos(index);
+            assert_eq!(index, folded.unfold(at..at + 1).start);
+        }
+        assert_eq!(folded.text.len(), folded.fold_pos(line.len()));
+        assert_eq!(vec![4..7], Pattern::new("ⱥC", false).find_spans(line));
+        assert_eq!(vec![7..10], Pattern::new("SSD", false).find_spans(line));
+        assert_eq!(vec![1..3, 4..6], Pattern::new("i", false).find_spans("xİyİz"));
+    }
+
+    #[test]
+    fn whole_word() {
+        let word = Pattern::Word(Box::new(Pattern::new("body", true)));
+        assert!(!word.is_match("nobody somebody"));
+        assert_eq!(vec![17..21], word.find_spans("nobody's, body_x body"));
+        assert!(!word.is_match("body_x"));
+
+        // 中文等非拉丁字母同样被视为单词字符
+        let word = Pattern::Word(Box::new(Pattern::new("参数", true)));
+        assert!(!word.is_match("命令行参数"));
+        assert!(word.is_match("参数 not enough"));
+
+        let word = Pattern::Word(Box::new(Pattern::Regex(Regex::new("-x|ab").unwrap())));
+        assert_eq!(vec![2..4, 9..11], word.find_spans("a -x bab ab"));
+    }
+
+    #[test]
+    fn pattern_set() {
+        let patterns: Vec<String> = ["fast", "safe", "Pick", "fast, productive"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let set = Pattern::literals(&patterns, true).unwrap();
+        assert!(matches!(set, Pattern::Literals(_)));
+        assert!(set.is_match("Pick three."));
+        assert!(!set.is_match("Duct tape."));
+        assert_eq!(vec![0..4, 6..22], set.find_spans("safe, fast, productive."));
+
+        let set = Pattern::literals(&patterns, false).unwrap();
+        assert_eq!(vec![0..4], set.find_spans("PICK three."));
+        let set = Pattern::literals(&[String::from("ß"), String::from("safe")], false).unwrap();
+        assert_eq!(vec![2..4, 6..10], set.find_spans("Fuß, SAFE"));
+
+        // 没有模式时什么都不匹配，只有一个模式时退化为普通的Pattern
+        assert!(!Pattern::literals(&[], true).unwrap().is_match("anything"));
+        assert!(matches!(Pattern::literals(&patterns[..1], false).unwrap(), Pattern::CaseInsensitive(_)));
+    }
+}
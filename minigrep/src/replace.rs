@@ -0,0 +1,183 @@
+// --replace/--in-place：把匹配到的文本替换掉。
+// 替换后的文件先完整地写进同一目录下的临时文件，再通过rename覆盖原文件，
+// 所以即使中途出错，原文件也不会只被写了一半。
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Config, Pattern};
+
+impl Pattern {
+    // 把line中所有匹配替换为replacement。正则模式下replacement中可以使用$1、${name}引用捕获组。
+    pub fn replace<'a>(&self, line: &'a str, replacement: &str) -> Cow<'a, str> {
+        match self {
+            Pattern::Regex(re) => re.replace_all(line, replacement),
+            _ => {
+                let spans = self.find_spans(line);
+                if spans.is_empty() {
+                    return Cow::Borrowed(line);
+                }
+                // -w -E时捕获组仍然可用：在每个通过了单词边界检查的位置重新取出捕获组
+                let captures = match self {
+                    Pattern::Word(inner) => match inner.as_ref() {
+                        Pattern::Regex(re) => Some(re),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let mut replaced = String::with_capacity(line.len());
+                let mut pos = 0;
+                for span in spans {
+                    replaced.push_str(&line[pos..span.start]);
+                    match captures.and_then(|re| re.captures_at(line, span.start)) {
+                        Some(caps) => caps.expand(replacement, &mut replaced),
+                        None => replaced.push_str(replacement),
+                    }
+                    pos = span.end;
+                }
+                replaced.push_str(&line[pos..]);
+                Cow::Owned(replaced)
+            }
+        }
+    }
+}
+
+// 对一个文件执行替换，返回被修改的行数。
+// dry_run时不修改文件，而是把修改以diff的形式打印到out。
+pub fn edit_file<W: Write>(config: &Config, path: &Path, out: &mut W) -> io::Result<usize> {
+    let replacement = config.replace.as_deref().unwrap_or("");
+    let contents = fs::read_to_string(path)?;
+
+    let mut edited = String::with_capacity(contents.len());
+    let mut changes = Vec::new();
+    for (index, raw) in contents.split_inclusive('\n').enumerate() {
+        // 换行符不参与匹配，替换后原样保留
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let ending = &raw[line.len()..];
+        let replaced = if config.pattern.is_match(line) {
+            config.pattern.replace(line, replacement)
+        } else {
+            Cow::Borrowed(line)
+        };
+        if replaced != line {
+            changes.push((index + 1, line, replaced.to_string()));
+        }
+        edited.push_str(&replaced);
+        edited.push_str(ending);
+    }
+
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    if config.dry_run {
+        print_diff(out, path, &changes)?;
+    } else {
+        write_atomically(path, &edited, config.backup_suffix.as_deref())?;
+    }
+    Ok(changes.len())
+}
+
+// 按照unified diff的样子打印每一处修改
+fn print_diff<W: Write>(out: &mut W, path: &Path, changes: &[(usize, &str, String)]) -> io::Result<()> {
+    writeln!(out, "--- {}", path.display())?;
+    writeln!(out, "+++ {}", path.display())?;
+    for (line_number, old, new) in changes {
+        writeln!(out, "@@ -{0},1 +{0},1 @@", line_number)?;
+        writeln!(out, "-{}", old)?;
+        writeln!(out, "+{}", new)?;
+    }
+    Ok(())
+}
+
+fn write_atomically(path: &Path, contents: &str, backup_suffix: Option<&str>) -> io::Result<()> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+    let file_name = file_name.unwrap_or_default();
+    let tmp_path = path.with_file_name(format!(".{}.minigrep-tmp", file_name));
+
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(contents.as_bytes())?;
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        // 新文件沿用原文件的权限
+        fs::set_permissions(&tmp_path, fs::metadata(path)?.permissions())?;
+
+        if let Some(suffix) = backup_suffix {
+            fs::copy(path, backup_path(path, suffix))?;
+        }
+        fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    PathBuf::from(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn replace_literal_and_case_insensitive() {
+        assert_eq!("a nobody, nobody", Pattern::new("body", true).replace("a body, body", "nobody"));
+        assert_eq!("x-ray x", Pattern::new("rUsT", false).replace("Rust-ray RUST", "x"));
+    }
+
+    #[test]
+    fn replace_with_captures() {
+        let pattern = Pattern::Regex(Regex::new(r"(?P<h>\d{2}):(\d{2})").unwrap());
+        assert_eq!("at 30h10", pattern.replace("at 10:30", "${2}h$h"));
+
+        // -w时只替换完整的单词，捕获组同样可以引用
+        let word = Pattern::Word(Box::new(Pattern::Regex(Regex::new(r"(\d+):(\d+)").unwrap())));
+        assert_eq!("at 30h10 ok x1:2", word.replace("at 10:30 ok x1:2", "${2}h${1}"));
+    }
+
+    #[test]
+    fn edit_file_in_place() {
+        let dir = std::env::temp_dir().join(format!("minigrep-replace-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("poem.txt");
+        fs::write(&path, "I'm nobody!\r\nWho are you?\nAre you nobody, too?").unwrap();
+
+        let file = path.display().to_string();
+        let args = ["minigrep", "--replace", "somebody", "--in-place=.bak", "nobody", &file];
+        let config = crate::tests::parse(&args).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(2, edit_file(&config, &path, &mut out).unwrap());
+        assert!(out.is_empty());
+        assert_eq!(
+            "I'm somebody!\r\nWho are you?\nAre you somebody, too?",
+            fs::read_to_string(&path).unwrap()
+        );
+        assert_eq!(
+            "I'm nobody!\r\nWho are you?\nAre you nobody, too?",
+            fs::read_to_string(backup_path(&path, ".bak")).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_prints_diff() {
+        let mut out = Vec::new();
+        let changes = [(2, "Are you nobody, too?", String::from("Are you somebody, too?"))];
+        print_diff(&mut out, Path::new("poem.txt"), &changes).unwrap();
+        assert_eq!(
+            "--- poem.txt\n+++ poem.txt\n@@ -2,1 +2,1 @@\n-Are you nobody, too?\n+Are you somebody, too?\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,415 @@
+// 供其他crate嵌入使用的搜索接口，不向标准输出打印任何内容：
+// • Matcher：判断一行中哪些部分匹配。Pattern实现了它（Pattern::new是区分或不区分大小写的字面量，
+//   Pattern::regex是正则表达式），regex::Regex也可以直接使用，调用者还可以为自己的类型实现它
+// • Sink：接收匹配行、上下文行等事件，Vec<MatchBuf>就是一个只收集匹配行的Sink
+// • Searcher：由SearcherBuilder配置上下文行数、-v、-m等选项，逐行搜索字符串、reader或者文件
+// 命令行自己的搜索也是一个Searcher加上一个打印结果的Sink，上下文的处理只有Context这一份实现。
+//
+//   let searcher = Searcher::builder().context(1).max_count(Some(10)).build();
+//   let mut lines: Vec<MatchBuf> = Vec::new();
+//   searcher.search_str(&Pattern::new("duct", false), contents, &mut lines)?;
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::{for_each_line, Match, MatchBuf, Pattern};
+
+pub trait Matcher {
+    // 从pos开始查找第一个匹配，pos总是位于字符边界上
+    fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, haystack: &str) -> bool {
+        self.find_at(haystack, 0).is_some()
+    }
+
+    // 所有不重叠的匹配，空匹配不包含在内
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        while let Some(span) = self.find_at(haystack, pos) {
+            if span.is_empty() {
+                match haystack[span.end..].chars().next() {
+                    Some(c) => pos = span.end + c.len_utf8(),
+                    None => break,
+                }
+            } else {
+                pos = span.end;
+                spans.push(span);
+            }
+        }
+        spans
+    }
+}
+
+impl Matcher for Pattern {
+    fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>> {
+        Pattern::find_at(self, haystack, pos)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        Pattern::is_match(self, haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        Pattern::find_spans(self, haystack)
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>> {
+        Regex::find_at(self, haystack, pos).map(|m| m.range())
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        Regex::is_match(self, haystack)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for &M {
+    fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, pos)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        (**self).find_spans(haystack)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>> {
+        (**self).find_at(haystack, pos)
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        (**self).is_match(haystack)
+    }
+
+    fn find_spans(&self, haystack: &str) -> Vec<Range<usize>> {
+        (**self).find_spans(haystack)
+    }
+}
+
+// 上下文行位于匹配行之前还是之后
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextKind {
+    Before,
+    After,
+}
+
+// 搜索过程中的事件按行的顺序依次送达，返回Ok(false)的事件会让搜索提前结束
+pub trait Sink {
+    // 被选中的行，spans是其中匹配的部分，-v选中的行没有匹配
+    fn matched(&mut self, line: &Match, spans: &[Range<usize>]) -> io::Result<bool>;
+
+    fn context(&mut self, _line: &Match, _kind: ContextKind) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    // 有上下文时，接下来的行和上一次送达的行不相邻，相当于命令行输出中的"--"
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // 返回false时matched收到的spans总是空的，不需要匹配位置的Sink可以省去这部分查找
+    fn needs_spans(&self) -> bool {
+        true
+    }
+
+    // 搜索结束，selected是被选中的行数
+    fn finish(&mut self, _selected: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// 只收集匹配行，忽略上下文
+impl Sink for Vec<MatchBuf> {
+    fn matched(&mut self, line: &Match, _spans: &[Range<usize>]) -> io::Result<bool> {
+        self.push(MatchBuf::from(line));
+        Ok(true)
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    fn matched(&mut self, line: &Match, spans: &[Range<usize>]) -> io::Result<bool> {
+        (**self).matched(line, spans)
+    }
+
+    fn context(&mut self, line: &Match, kind: ContextKind) -> io::Result<bool> {
+        (**self).context(line, kind)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        (**self).context_break()
+    }
+
+    fn needs_spans(&self) -> bool {
+        (**self).needs_spans()
+    }
+
+    fn finish(&mut self, selected: usize) -> io::Result<()> {
+        (**self).finish(selected)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    before_context: usize,
+    after_context: usize,
+    invert: bool,
+    max_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearcherBuilder {
+    searcher: Searcher,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    // 和-B相同
+    pub fn before_context(mut self, lines: usize) -> SearcherBuilder {
+        self.searcher.before_context = lines;
+        self
+    }
+
+    // 和-A相同
+    pub fn after_context(mut self, lines: usize) -> SearcherBuilder {
+        self.searcher.after_context = lines;
+        self
+    }
+
+    // 和-C相同
+    pub fn context(self, lines: usize) -> SearcherBuilder {
+        self.before_context(lines).after_context(lines)
+    }
+
+    // 和-v相同：选中不匹配的行
+    pub fn invert(mut self, invert: bool) -> SearcherBuilder {
+        self.searcher.invert = invert;
+        self
+    }
+
+    // 和-m相同：最多选中多少行，None表示不限制
+    pub fn max_count(mut self, max_count: Option<usize>) -> SearcherBuilder {
+        self.searcher.max_count = max_count;
+        self
+    }
+
+    pub fn build(self) -> Searcher {
+        self.searcher
+    }
+}
+
+impl Searcher {
+    // 没有上下文、不限制行数
+    pub fn new() -> Searcher {
+        Searcher::default()
+    }
+
+    pub fn builder() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+
+    // 以下几个方法都返回被选中的行数
+    pub fn search_str<M, S>(&self, matcher: &M, contents: &str, sink: S) -> io::Result<usize>
+    where
+        M: Matcher + ?Sized,
+        S: Sink,
+    {
+        self.search_reader(matcher, contents.as_bytes(), sink)
+    }
+
+    pub fn search_path<M, S>(&self, matcher: &M, path: &Path, sink: S) -> io::Result<usize>
+    where
+        M: Matcher + ?Sized,
+        S: Sink,
+    {
+        self.search_reader(matcher, BufReader::new(File::open(path)?), sink)
+    }
+
+    pub fn search_reader<M, R, S>(&self, matcher: &M, reader: R, mut sink: S) -> io::Result<usize>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink,
+    {
+        let mut context = Context::new(self.before_context, self.after_context);
+        let mut count = 0;
+        for_each_line(reader, |line| {
+            if self.max_count.is_some_and(|max| count >= max) {
+                // 达到上限之后，只把剩余的后置上下文送完
+                if !context.in_after_context() {
+                    return Ok(false);
+                }
+                return context.other(&mut sink, line);
+            }
+            if matcher.is_match(line.line) == self.invert {
+                return context.other(&mut sink, line);
+            }
+
+            count += 1;
+            let spans = if self.invert || !sink.needs_spans() {
+                Vec::new()
+            } else {
+                matcher.find_spans(line.line)
+            };
+            context.selected(&mut sink, line, &spans)
+        })?;
+        sink.finish(count)?;
+        Ok(count)
+    }
+}
+
+// 上下文的状态机：决定哪些没有被选中的行作为前置或后置上下文送给Sink，以及什么时候需要context_break。
+// Searcher和命令行的Printer都使用它
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Context {
+    before_context: usize,
+    after_context: usize,
+    // 最近的before_context个没有送出的行
+    before: VecDeque<MatchBuf>,
+    after_left: usize,
+    // 上一个送达Sink的行号
+    last: Option<usize>,
+}
+
+impl Context {
+    pub(crate) fn new(before_context: usize, after_context: usize) -> Context {
+        Context {
+            before_context,
+            after_context,
+            ..Context::default()
+        }
+    }
+
+    // 是否还有没送完的后置上下文
+    pub(crate) fn in_after_context(&self) -> bool {
+        self.after_left > 0
+    }
+
+    // 被选中的行：先送出缓存的前置上下文
+    pub(crate) fn selected<S: Sink + ?Sized>(
+        &mut self,
+        sink: &mut S,
+        line: &Match,
+        spans: &[Range<usize>],
+    ) -> io::Result<bool> {
+        let first = self.before.front().map_or(line.line_number, |line| line.line_number);
+        let has_context = self.before_context > 0 || self.after_context > 0;
+        if has_context && self.last.is_some_and(|last| first > last + 1) {
+            sink.context_break()?;
+        }
+        while let Some(context) = self.before.pop_front() {
+            if !sink.context(&context.as_match(), ContextKind::Before)? {
+                return Ok(false);
+            }
+        }
+        self.last = Some(line.line_number);
+        self.after_left = self.after_context;
+        sink.matched(line, spans)
+    }
+
+    // 没有被选中的行：作为后置上下文送出，或者缓存起来留作前置上下文
+    pub(crate) fn other<S: Sink + ?Sized>(&mut self, sink: &mut S, line: &Match) -> io::Result<bool> {
+        if self.after_left > 0 {
+            self.after_left -= 1;
+            self.last = Some(line.line_number);
+            return sink.context(line, ContextKind::After);
+        }
+        if self.before_context > 0 {
+            if self.before.len() == self.before_context {
+                self.before.pop_front();
+            }
+            self.before.push_back(MatchBuf::from(line));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 把所有事件记录成文本，方便比较
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn matched(&mut self, line: &Match, spans: &[Range<usize>]) -> io::Result<bool> {
+            self.0.push(format!("{}:{} {:?}", line.line_number, line.line, spans));
+            Ok(true)
+        }
+
+        fn context(&mut self, line: &Match, kind: ContextKind) -> io::Result<bool> {
+            self.0.push(format!("{}-{} {:?}", line.line_number, line.line, kind));
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.0.push(String::from("--"));
+            Ok(())
+        }
+
+        fn finish(&mut self, selected: usize) -> io::Result<()> {
+            self.0.push(format!("finish {}", selected));
+            Ok(())
+        }
+    }
+
+    // 调用者自己实现的Matcher：只由数字组成的行
+    struct Digits;
+
+    impl Matcher for Digits {
+        fn find_at(&self, haystack: &str, pos: usize) -> Option<Range<usize>> {
+            let all_digits = !haystack.is_empty() && haystack.bytes().all(|b| b.is_ascii_digit());
+            (pos == 0 && all_digits).then_some(0..haystack.len())
+        }
+    }
+
+    const CONTENTS: &str = "a\nb\nduct\nc\nd\ne\nDuct\nf";
+
+    #[test]
+    fn context_events() {
+        let mut events = Events::default();
+        let searcher = Searcher::builder().context(1).build();
+        let count = searcher.search_str(&Pattern::new("duct", false), CONTENTS, &mut events).unwrap();
+        assert_eq!(2, count);
+        assert_eq!(
+            vec![
+                "2-b Before", "3:duct [0..4]", "4-c After", "--", "6-e Before", "7:Duct [0..4]", "8-f After",
+                "finish 2",
+            ],
+            events.0
+        );
+    }
+
+    #[test]
+    fn matchers_and_options() {
+        let mut lines: Vec<MatchBuf> = Vec::new();
+        let re = Regex::new("^[a-c]$").unwrap();
+        let searcher = Searcher::builder().max_count(Some(2)).build();
+        assert_eq!(2, searcher.search_str(&re, CONTENTS, &mut lines).unwrap());
+        assert_eq!(vec!["a", "b"], lines.iter().map(|line| line.line.as_str()).collect::<Vec<_>>());
+
+        let mut lines: Vec<MatchBuf> = Vec::new();
+        let boxed: Box<dyn Matcher> = Box::new(Pattern::regex("^D", false).unwrap());
+        let searcher = Searcher::builder().invert(true).build();
+        assert_eq!(5, searcher.search_str(&boxed, CONTENTS, &mut lines).unwrap());
+
+        let mut events = Events::default();
+        Searcher::new().search_str(&Digits, "12\n1a\n345", &mut events).unwrap();
+        assert_eq!(vec!["1:12 [0..2]", "3:345 [0..3]", "finish 2"], events.0);
+    }
+}
@@ -0,0 +1,176 @@
+// 流式搜索：逐行从BufRead读取内容，任何时候内存中都只保留当前这一行，
+// 因此可以处理几个GB的日志文件，也可以处理永远不会结束的标准输入。
+
+use std::io::{self, BufRead};
+use std::ops::Range;
+use std::str;
+
+use crate::{Match, Pattern};
+
+// 读取一行到buf中，返回读取的字节数（包含换行符）以及去掉换行符之后的内容，读到末尾时返回None
+fn read_line<'b, R: BufRead>(reader: &mut R, buf: &'b mut Vec<u8>) -> io::Result<Option<(usize, &'b str)>> {
+    buf.clear();
+    let read = reader.read_until(b'\n', buf)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    let raw = str::from_utf8(buf).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let line = raw.strip_suffix('\n').unwrap_or(raw);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    Ok(Some((read, line)))
+}
+
+// 逐行读取reader并交给f处理，f返回false时提前停止读取
+pub fn for_each_line<R, F>(mut reader: R, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(&Match) -> io::Result<bool>,
+{
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+    let mut offset = 0;
+    while let Some((read, line)) = read_line(&mut reader, &mut buf)? {
+        line_number += 1;
+        let go_on = f(&Match {
+            line_number,
+            byte_range: offset..offset + line.len(),
+            line,
+        })?;
+        if !go_on {
+            break;
+        }
+        offset += read;
+    }
+    Ok(())
+}
+
+// Match的所有权版本，流式搜索时行的内容无法借用自某个完整的字符串
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchBuf {
+    pub line_number: usize,
+    pub byte_range: Range<usize>,
+    pub line: String,
+}
+
+impl MatchBuf {
+    pub fn as_match(&self) -> Match<'_> {
+        Match {
+            line_number: self.line_number,
+            byte_range: self.byte_range.clone(),
+            line: &self.line,
+        }
+    }
+}
+
+impl From<&Match<'_>> for MatchBuf {
+    fn from(line: &Match) -> MatchBuf {
+        MatchBuf {
+            line_number: line.line_number,
+            byte_range: line.byte_range.clone(),
+            line: line.line.to_string(),
+        }
+    }
+}
+
+// 逐个产出匹配行的迭代器，由search_reader创建
+pub struct SearchReader<'p, R> {
+    reader: R,
+    pattern: &'p Pattern,
+    buf: Vec<u8>,
+    line_number: usize,
+    offset: usize,
+}
+
+pub fn search_reader<R: BufRead>(pattern: &Pattern, reader: R) -> SearchReader<'_, R> {
+    SearchReader {
+        reader,
+        pattern,
+        buf: Vec::new(),
+        line_number: 0,
+        offset: 0,
+    }
+}
+
+impl<R: BufRead> SearchReader<'_, R> {
+    fn next_match(&mut self) -> io::Result<Option<MatchBuf>> {
+        while let Some((read, line)) = read_line(&mut self.reader, &mut self.buf)? {
+            self.line_number += 1;
+            let start = self.offset;
+            self.offset += read;
+
+            if self.pattern.is_match(line) {
+                return Ok(Some(MatchBuf {
+                    line_number: self.line_number,
+                    byte_range: start..start + line.len(),
+                    line: line.to_string(),
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for SearchReader<'_, R> {
+    type Item = io::Result<MatchBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_match().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streaming_lines() {
+        let input: &[u8] = b"Rust:\r\nsafe, fast, productive.\nPick three.";
+        let mut lines = Vec::new();
+        for_each_line(input, |line| {
+            lines.push((line.line_number, line.byte_range.clone(), line.line.to_string()));
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(
+            vec![
+                (1, 0..5, String::from("Rust:")),
+                (2, 7..30, String::from("safe, fast, productive.")),
+                (3, 31..42, String::from("Pick three.")),
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn search_reader_case_insensitive() {
+        let pattern = Pattern::new("rUsT", false);
+        let input: &[u8] = b"Rust:\nsafe, fast, productive.\nPick three.\nTrust me.\n";
+        let matches: Vec<MatchBuf> = search_reader(&pattern, input)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            vec![
+                MatchBuf {
+                    line_number: 1,
+                    byte_range: 0..5,
+                    line: String::from("Rust:"),
+                },
+                MatchBuf {
+                    line_number: 4,
+                    byte_range: 42..51,
+                    line: String::from("Trust me."),
+                },
+            ],
+            matches
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error() {
+        let input: &[u8] = b"ok\n\xff\xfe\n";
+        let pattern = Pattern::new("ok", true);
+        let results: Vec<_> = search_reader(&pattern, input).collect();
+        assert!(results[0].is_ok());
+        assert_eq!(io::ErrorKind::InvalidData, results[1].as_ref().unwrap_err().kind());
+    }
+}
@@ -0,0 +1,102 @@
+// 文件类型：把一个名称对应到一组glob，-t只搜索某些类型的文件，-T跳过某些类型的文件。
+// 除了内置的类型，还可以在配置文件的[types]中或者用--type-add name:glob[,glob...]定义新类型，
+// 已有的类型会追加新的glob。
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css", "*.scss"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json", "*.jsonl"]),
+    ("log", &["*.log"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+// 按名称排序，--type-list的输出因此是稳定的
+pub struct Types {
+    defs: BTreeMap<String, Vec<String>>,
+}
+
+impl Types {
+    // 只包含内置类型
+    pub fn new() -> Types {
+        let defs = BUILTIN
+            .iter()
+            .map(|(name, globs)| (name.to_string(), globs.iter().map(|g| g.to_string()).collect()))
+            .collect();
+        Types { defs }
+    }
+
+    pub fn add(&mut self, name: &str, globs: &[String]) {
+        self.defs.entry(name.to_string()).or_default().extend(globs.iter().cloned());
+    }
+
+    // 解析--type-add的值，格式不对时返回None
+    pub fn add_spec(&mut self, spec: &str) -> Option<()> {
+        let (name, globs) = spec.split_once(':')?;
+        let globs: Vec<String> = globs.split(',').filter(|g| !g.is_empty()).map(String::from).collect();
+        if name.is_empty() || globs.is_empty() {
+            return None;
+        }
+        self.add(name, &globs);
+        Some(())
+    }
+
+    pub fn globs(&self, name: &str) -> Option<&[String]> {
+        self.defs.get(name).map(Vec::as_slice)
+    }
+
+    // --type-list：每行一个类型，比如"rust: *.rs"
+    pub fn list<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (name, globs) in &self.defs {
+            writeln!(out, "{}: {}", name, globs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Types {
+    fn default() -> Types {
+        Types::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_and_custom_types() {
+        let mut types = Types::new();
+        assert_eq!(Some(&[String::from("*.rs")][..]), types.globs("rust"));
+        assert_eq!(None, types.globs("web"));
+
+        assert_eq!(Some(()), types.add_spec("web:*.html,*.css"));
+        assert_eq!(Some(()), types.add_spec("rust:build.rs.in"));
+        assert_eq!(None, types.add_spec("web"));
+        assert_eq!(None, types.add_spec(":*.x"));
+        assert_eq!(2, types.globs("web").unwrap().len());
+        assert_eq!(2, types.globs("rust").unwrap().len());
+
+        let mut out = Vec::new();
+        types.list(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\nrust: *.rs, build.rs.in\n"));
+        assert!(out.contains("\nweb: *.html, *.css\n"));
+    }
+}
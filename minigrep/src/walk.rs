@@ -0,0 +1,197 @@
+// 把命令行给出的路径展开为需要搜索的文件列表。
+// 目录会被递归遍历，遍历时只收集普通文件（不跟随符号链接），
+// 并根据--include/--exclude过滤。命令行直接给出的文件总是会被搜索。
+// 默认还会跳过隐藏文件（--hidden时不跳过）以及.gitignore、.ignore和git全局排除文件中列出的路径，
+// --no-ignore关闭这两项。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::Glob;
+use crate::ignore::{self, IgnoreFile};
+
+#[derive(Default)]
+pub struct Filter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    // 是否搜索以'.'开头的隐藏文件和目录
+    pub hidden: bool,
+    // 是否遵守.gitignore等规则文件
+    pub ignore_files: bool,
+    // git的全局排除文件，由调用者用ignore::global_excludes加载，walk_paths本身不读取家目录
+    pub global_excludes: Option<IgnoreFile>,
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Filter {
+        Filter {
+            include: include.iter().map(|p| Glob::new(p)).collect(),
+            exclude: exclude.iter().map(|p| Glob::new(p)).collect(),
+            hidden: false,
+            ignore_files: true,
+            global_excludes: None,
+        }
+    }
+
+    fn is_excluded(&self, rel_path: &str, is_dir: bool) -> bool {
+        self.exclude.iter().any(|g| g.is_match(rel_path, is_dir))
+    }
+
+    // --include只约束文件，不会阻止进入目录
+    fn accepts_file(&self, rel_path: &str) -> bool {
+        if self.is_excluded(rel_path, false) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|g| g.is_match(rel_path, false))
+    }
+}
+
+// 返回值中的文件按照路径参数的顺序排列，同一目录下的条目按名称排序，保证输出稳定。
+// 无法访问的路径或目录交给on_error，然后继续处理其余的路径
+pub fn walk_paths<F: FnMut(&Path, io::Error)>(paths: &[String], filter: &Filter, mut on_error: F) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path == Path::new(crate::STDIN_PATH) {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                let mut ignores = Vec::new();
+                if filter.ignore_files {
+                    ignores.extend(filter.global_excludes.clone());
+                    ignore::load_parents(path, &mut ignores);
+                }
+                walk_dir(path, "", filter, &mut ignores, &mut files, &mut on_error);
+            }
+            Ok(_) => files.push(path.to_path_buf()),
+            Err(err) => on_error(path, err),
+        }
+    }
+    files
+}
+
+// ignores是从外到内各级目录中的规则文件，进入dir时追加dir中的规则文件，离开时移除
+fn walk_dir<F: FnMut(&Path, io::Error)>(
+    dir: &Path,
+    rel: &str,
+    filter: &Filter,
+    ignores: &mut Vec<IgnoreFile>,
+    files: &mut Vec<PathBuf>,
+    on_error: &mut F,
+) {
+    let mut entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<Result<Vec<_>, _>>()) {
+        Ok(entries) => entries,
+        Err(err) => return on_error(dir, err),
+    };
+    let loaded = ignores.len();
+    if filter.ignore_files {
+        ignore::load_dir(dir, rel, "", ignores);
+    }
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        // .git目录总是被跳过，除非使用了--no-ignore
+        if (name.starts_with('.') && !filter.hidden) || (name == ".git" && filter.ignore_files) {
+            continue;
+        }
+        let child_rel = if rel.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", rel, name)
+        };
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(err) => {
+                on_error(&entry.path(), err);
+                continue;
+            }
+        };
+        if ignore::is_ignored(ignores, &child_rel, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            if !filter.is_excluded(&child_rel, true) {
+                walk_dir(&entry.path(), &child_rel, filter, ignores, files, on_error);
+            }
+        } else if file_type.is_file() && filter.accepts_file(&child_rel) {
+            files.push(entry.path());
+        }
+    }
+    ignores.truncate(loaded);
+}
+
+// 和grep一样，只要文件开头的一段内容里出现了NUL字节，就认为它是二进制文件
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|b| *b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_include_and_exclude() {
+        let filter = Filter::new(&[String::from("*.rs")], &[String::from("target/")]);
+        assert!(filter.accepts_file("src/lib.rs"));
+        assert!(!filter.accepts_file("poem.txt"));
+        assert!(filter.is_excluded("target", true));
+        assert!(!filter.is_excluded("src", true));
+    }
+
+    #[test]
+    fn hidden_and_ignored_files() {
+        let root = std::env::temp_dir().join(format!("minigrep-walk-{}", std::process::id()));
+        for dir in ["src", "target/debug", "node_modules/x", ".cache", "docs"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "src/lib.rs", "target/debug/out.rs", "node_modules/x/index.js", ".cache/a",
+            ".env", "docs/a.log", "docs/keep.log",
+        ] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join(".ignore"), "node_modules\n").unwrap();
+        fs::write(root.join("docs/.gitignore"), "!keep.log\n").unwrap();
+
+        let walk = |filter: &Filter| -> Vec<String> {
+            walk_paths(&[root.display().to_string()], filter, |path, err| panic!("{}: {}", path.display(), err))
+                .iter()
+                .map(|path| path.strip_prefix(&root).unwrap().display().to_string())
+                .collect()
+        };
+        let mut filter = Filter::new(&[], &[]);
+        assert_eq!(vec!["docs/keep.log", "src/lib.rs"], walk(&filter));
+        filter.hidden = true;
+        assert_eq!(
+            vec![".cache/a", ".env", ".gitignore", ".ignore", "docs/.gitignore", "docs/keep.log", "src/lib.rs"],
+            walk(&filter)
+        );
+        // 全局排除文件的优先级最低，docs/.gitignore中的!keep.log仍然有效
+        filter.global_excludes = Some(IgnoreFile::parse("*.rs\nkeep.log\n", "", ""));
+        assert_eq!(
+            vec![".cache/a", ".env", ".gitignore", ".ignore", "docs/.gitignore", "docs/keep.log"],
+            walk(&filter)
+        );
+        // --no-ignore
+        let filter = Filter {
+            hidden: true,
+            ..Filter::default()
+        };
+        assert_eq!(10, walk(&filter).len());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(is_binary(b"ELF\0\x01\x02"));
+        assert!(!is_binary("普通的文本\n".as_bytes()));
+    }
+}
@@ -0,0 +1,324 @@
+// --watch和--follow：搜索完之后不退出，而是定期检查文件的变化（轮询，不依赖操作系统的通知机制）。
+// • --watch：任何文件的修改时间或大小变化、或者有文件被添加、删除时，重新完整地搜索一遍
+// • --follow：像tail -f一样，只搜索追加到文件末尾的新行；文件变短时（被截断或轮转）从头开始，
+//   之后新出现的文件也从头开始搜索
+// 两种模式都会一直运行，直到被Ctrl-C等信号终止。
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::output::Printer;
+use crate::{file_filter, for_each_line, make_printer, search_all, walk, Config, Match, MinigrepError, Outcome};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// 每个文件的修改时间和大小
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+// 每次轮询时要检查的文件。暂时不存在（比如正在被重新生成）或者无法读取的路径被跳过，
+// 它们之后出现时就会被发现；其他路径照常检查。错误每次轮询都会出现，所以不打印
+fn current_files(config: &Config) -> Vec<PathBuf> {
+    walk::walk_paths(&config.paths, &file_filter(config), |_, _| {})
+}
+
+fn snapshot(config: &Config) -> Snapshot {
+    current_files(config)
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok()?;
+            Some((path, (metadata.modified().ok(), metadata.len())))
+        })
+        .collect()
+}
+
+// 两种模式都只会因为出错而返回，标准输出被关闭时也会返回
+pub fn watch(config: &Arc<Config>) -> Result<(), MinigrepError> {
+    let mut last = snapshot(config);
+    loop {
+        let mut outcome = Outcome {
+            matched: false,
+            errors: 0,
+            quiet: config.quiet,
+        };
+        match search_all(config, &mut outcome) {
+            Ok(()) => {}
+            Err(err) if err.is_broken_pipe() => return Err(err),
+            // 某一次搜索出错（比如文件在搜索过程中被删除）不应该结束监视
+            Err(err) => eprintln!("minigrep: {}", err.report()),
+        }
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let next = snapshot(config);
+            if next != last {
+                last = next;
+                break;
+            }
+        }
+        eprintln!("minigrep: files changed, searching again");
+    }
+}
+
+pub fn follow(config: &Arc<Config>) -> Result<(), MinigrepError> {
+    let files = walk::walk_paths(&config.paths, &file_filter(config), |path, err| {
+        eprintln!("minigrep: {}", MinigrepError::from_io(path, err).report());
+    });
+    let printer = make_printer(config, &files);
+    // 启动时已经存在的文件从末尾开始，当时无法读取的文件之后会像新文件一样从头开始
+    let mut tails = BTreeMap::new();
+    for path in files {
+        match Tail::at_end(&path, printer.clone()) {
+            Ok(tail) => {
+                tails.insert(path, tail);
+            }
+            Err(err) => eprintln!("minigrep: {}", MinigrepError::from_io(&path, err).report()),
+        }
+    }
+
+    let stdout = io::stdout();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut out = stdout.lock();
+        for path in current_files(config) {
+            let tail = tails.entry(path.clone()).or_insert_with(|| Tail::new(printer.clone()));
+            match tail.poll(config, &path, &mut out) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Err(err.into()),
+                Err(err) => eprintln!("minigrep: {}", MinigrepError::from_io(&path, err).report()),
+            }
+        }
+        out.flush()?;
+    }
+}
+
+// 一个正在被跟踪的文件
+struct Tail {
+    // 已经完整处理过的字节数和行数
+    consumed: u64,
+    line_number: usize,
+    // 读到的最后一行还没有换行符，等它写完整之后再搜索
+    partial: Vec<u8>,
+    // 每个文件有自己的Printer，上下文不会在文件之间混在一起
+    printer: Printer,
+}
+
+impl Tail {
+    fn new(printer: Printer) -> Tail {
+        Tail {
+            consumed: 0,
+            line_number: 0,
+            partial: Vec::new(),
+            printer,
+        }
+    }
+
+    // 跳过文件现有的完整行，但仍然数出它们的行数，这样之后打印的行号才是正确的
+    fn at_end(path: &Path, printer: Printer) -> io::Result<Tail> {
+        let mut tail = Tail::new(printer);
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 || !line.ends_with(b"\n") {
+                break;
+            }
+            tail.consumed += read as u64;
+            tail.line_number += 1;
+        }
+        tail.printer.begin_file();
+        Ok(tail)
+    }
+
+    fn read_so_far(&self) -> u64 {
+        self.consumed + self.partial.len() as u64
+    }
+
+    fn poll<W: Write>(&mut self, config: &Config, path: &Path, out: &mut W) -> io::Result<()> {
+        let len = fs::metadata(path)?.len();
+        if len < self.read_so_far() {
+            // 文件被截断或者被新文件替换了
+            *self = Tail::new(self.printer.clone());
+            self.printer.begin_file();
+        }
+        let start = self.read_so_far();
+        if len == start {
+            return Ok(());
+        }
+
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        file.take(len - start).read_to_end(&mut self.partial)?;
+        let complete = match self.partial.iter().rposition(|b| *b == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(()),
+        };
+        let rest = self.partial.split_off(complete);
+        let lines = std::mem::replace(&mut self.partial, rest);
+        // 无论能否解码，这些行都算处理过了，否则一行坏数据会让之后的每次轮询都失败
+        let (first_line, first_offset) = (self.line_number, self.consumed as usize);
+        self.line_number += lines.iter().filter(|b| **b == b'\n').count();
+        self.consumed += lines.len() as u64;
+        let text = decode(config, &lines)?;
+
+        let highlight = (self.printer.colors.is_some() || self.printer.json) && !config.invert;
+        let printer = &mut self.printer;
+        for_each_line(text.as_bytes(), |line| {
+            let line = Match {
+                line_number: first_line + line.line_number,
+                byte_range: first_offset + line.byte_range.start..first_offset + line.byte_range.end,
+                line: line.line,
+            };
+            let selected = config.pattern.is_match(line.line) != config.invert;
+            // 和search_input一样，--replace时打印替换后的内容
+            if let (Some(replacement), true) = (&config.replace, selected && !config.invert) {
+                let replaced = config.pattern.replace(line.line, replacement);
+                let line = Match {
+                    line: &replaced,
+                    ..line
+                };
+                return printer.line(out, path, &line, true, &[]).map(|_| true);
+            }
+            let spans = if selected && highlight {
+                config.pattern.find_spans(line.line)
+            } else {
+                Vec::new()
+            };
+            printer.line(out, path, &line, selected, &spans)?;
+            Ok(true)
+        })?;
+        Ok(())
+    }
+}
+
+// 按照--encoding和--lossy把新增的完整行转成UTF-8。
+// Config::new只允许ASCII兼容的编码和--follow一起使用，这些编码中'\n'不会出现在多字节字符中间，
+// 所以按行切开的每一块都可以单独解码
+fn decode<'a>(config: &Config, bytes: &'a [u8]) -> io::Result<Cow<'a, str>> {
+    match config.encoding {
+        Some(encoding) => Ok(encoding.decode_without_bom_handling(bytes).0),
+        None if config.lossy => Ok(String::from_utf8_lossy(bytes)),
+        None => std::str::from_utf8(bytes)
+            .map(Cow::Borrowed)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::parse;
+    use crate::ConfigError;
+
+    #[test]
+    fn follow_appended_lines() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "ERROR old\nINFO ok\nERROR partial").unwrap();
+
+        let file = path.display().to_string();
+        let config = parse(&["minigrep", "--follow", "-n", "-b", "ERROR", &file]).unwrap();
+        let mut tail = Tail::at_end(&path, Printer::new(false, true, true, 0, 0)).unwrap();
+        let mut out = Vec::new();
+
+        // 旧的完整行不会被打印，不完整的最后一行写完之后才会被搜索
+        tail.poll(&config, &path, &mut out).unwrap();
+        assert!(out.is_empty());
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b" line\nINFO fine\nERROR new\nERR").unwrap();
+        tail.poll(&config, &path, &mut out).unwrap();
+        assert_eq!("3:18:ERROR partial line\n5:47:ERROR new\n", String::from_utf8(out).unwrap());
+
+        // 文件被截断后从头开始
+        fs::write(&path, "ERROR again\n").unwrap();
+        let mut out = Vec::new();
+        tail.poll(&config, &path, &mut out).unwrap();
+        assert_eq!("1:0:ERROR again\n", String::from_utf8(out).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn follow_options() {
+        let dir = std::env::temp_dir().join(format!("minigrep-follow-options-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "").unwrap();
+        let file = path.display().to_string();
+        let config = |list: &[&str]| {
+            let mut args = vec!["minigrep", "--follow"];
+            args.extend_from_slice(list);
+            args.push(&file);
+            parse(&args)
+        };
+        for flags in [&["-q", "x"][..], &["-m1", "x"], &["--encoding=utf-16le", "x"]] {
+            assert!(matches!(config(flags), Err(ConfigError::ConflictingFlags("--follow", _))));
+        }
+
+        // 不合法的UTF-8只报一次错，之后的行照常搜索
+        let strict = config(&["--replace", "Y", "x"]).unwrap();
+        let mut tail = Tail::at_end(&path, Printer::new(false, false, false, 0, 0)).unwrap();
+        let mut out = Vec::new();
+        fs::write(&path, b"x caf\xe9\n").unwrap();
+        assert!(tail.poll(&strict, &path, &mut out).is_err());
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"x ok\n").unwrap();
+        tail.poll(&strict, &path, &mut out).unwrap();
+        assert_eq!("Y ok\n", String::from_utf8(out).unwrap());
+
+        let latin1 = config(&["-n", "--encoding=latin1", "café"]).unwrap();
+        let mut tail = Tail::at_end(&path, Printer::new(false, true, false, 0, 0)).unwrap();
+        let mut out = Vec::new();
+        file.write_all(b"caf\xe9\n").unwrap();
+        tail.poll(&latin1, &path, &mut out).unwrap();
+        assert_eq!("3:café\n", String::from_utf8(out).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_changes() {
+        let dir = std::env::temp_dir().join(format!("minigrep-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "a").unwrap();
+        let root = dir.display().to_string();
+        let config = parse(&["minigrep", "--watch", "x", &root]).unwrap();
+
+        let before = snapshot(&config);
+        assert_eq!(1, before.len());
+        assert_eq!(before, snapshot(&config));
+        fs::write(dir.join("a.txt"), "ab").unwrap();
+        assert_ne!(before, snapshot(&config));
+        fs::write(dir.join("b.txt"), "b").unwrap();
+        assert_eq!(2, snapshot(&config).len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_paths_are_skipped() {
+        let dir = std::env::temp_dir().join(format!("minigrep-watch-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (app, missing) = (dir.join("app.log"), dir.join("missing.log"));
+        fs::write(&app, "a").unwrap();
+        let paths = [app.display().to_string(), missing.display().to_string()];
+        let config = parse(&["minigrep", "--follow", "ERROR", &paths[0], &paths[1]]).unwrap();
+
+        // 不存在的路径不影响其他路径的检查，它出现之后也会被发现
+        assert_eq!(vec![app.clone()], current_files(&config));
+        let before = snapshot(&config);
+        assert_eq!(1, before.len());
+        fs::write(&app, "ab").unwrap();
+        assert_ne!(before, snapshot(&config));
+        fs::write(&missing, "m").unwrap();
+        assert_eq!(vec![app, missing], current_files(&config));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}